@@ -0,0 +1,15 @@
+// Command line flags, shared by every day that takes them
+
+// Every value given for `flag`, as in `--flag A --flag B`
+fn flag_values(flag: &str) -> Vec<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .zip(args.iter().skip(1))
+        .filter(|(arg, _)| *arg == flag)
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+fn flag_value(flag: &str) -> Option<String> {
+    flag_values(flag).into_iter().next()
+}
@@ -0,0 +1,203 @@
+// Reads the letters the AoC puzzles draw. Shared by day8 and day11.
+
+use std::collections::BTreeMap;
+
+use anyhow::{format_err, Result};
+
+// Every puzzle that draws letters uses this font. Most glyphs are 4 pixels
+// wide, a few are 5, and all of them are 6 tall.
+const HEIGHT: usize = 6;
+
+const FONT: &[(char, [&str; HEIGHT])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+// The columns of a glyph, with blank ones on either side dropped
+fn trim(columns: Vec<Vec<bool>>) -> Vec<Vec<bool>> {
+    let lit = |column: &Vec<bool>| column.iter().any(|pixel| *pixel);
+    let start = columns.iter().position(lit).unwrap_or(columns.len());
+    let end = columns.iter().rposition(lit).map_or(start, |end| end + 1);
+
+    columns[start..end].to_vec()
+}
+
+fn font_columns(rows: &[&str; HEIGHT]) -> Vec<Vec<bool>> {
+    let width = rows[0].len();
+    let columns = (0..width)
+        .map(|x| rows.iter().map(|row| row.as_bytes()[x] == b'#').collect())
+        .collect();
+
+    trim(columns)
+}
+
+// Reads the letters in a bitmap, given as rows of lit pixels. Glyphs are
+// split on blank columns, so they can sit at any offset.
+pub fn recognize(rows: &[Vec<bool>]) -> Result<String> {
+    if rows.len() != HEIGHT {
+        return Err(format_err!(
+            "Letters are {} pixels tall, but the image has {} row(s)",
+            HEIGHT,
+            rows.len()
+        ));
+    }
+
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let column = |x: usize| -> Vec<bool> {
+        rows.iter()
+            .map(|row| row.get(x).cloned().unwrap_or(false))
+            .collect()
+    };
+
+    // (first column, columns) for each run of non-blank columns
+    let mut glyphs: Vec<(usize, Vec<Vec<bool>>)> = Vec::new();
+    let mut current: Option<(usize, Vec<Vec<bool>>)> = None;
+    for x in 0..width {
+        let pixels = column(x);
+        if pixels.iter().any(|pixel| *pixel) {
+            current.get_or_insert((x, Vec::new())).1.push(pixels);
+        } else if let Some(glyph) = current.take() {
+            glyphs.push(glyph);
+        }
+    }
+    glyphs.extend(current);
+
+    let font: Vec<(char, Vec<Vec<bool>>)> = FONT
+        .iter()
+        .map(|(letter, rows)| (*letter, font_columns(rows)))
+        .collect();
+
+    let mut text = String::new();
+    let mut unrecognized = Vec::new();
+    for (i, (x, columns)) in glyphs.into_iter().enumerate() {
+        match font.iter().find(|(_, glyph)| *glyph == columns) {
+            Some((letter, _)) => text.push(*letter),
+            None => unrecognized.push(format!("#{} at column {}", i + 1, x)),
+        }
+    }
+
+    if !unrecognized.is_empty() {
+        return Err(format_err!(
+            "Unrecognized glyph(s) {} (read \"{}\" from the rest)",
+            unrecognized.join(", "),
+            text
+        ));
+    }
+
+    Ok(text)
+}
+
+// Reads the letters in a sparse grid of rows keyed by y, then x, with `lit`
+// picking the pixels that are on. Only the area around lit pixels is read.
+// Only day11 keeps its pixels in a grid.
+#[allow(dead_code)]
+pub fn recognize_grid<T>(
+    grid: &BTreeMap<i64, BTreeMap<i64, T>>,
+    lit: impl Fn(&T) -> bool,
+) -> Result<String> {
+    let points: Vec<(i64, i64)> = grid
+        .iter()
+        .flat_map(|(y, row)| {
+            row.iter()
+                .filter(|(_, value)| lit(value))
+                .map(move |(x, _)| (*x, *y))
+        })
+        .collect();
+
+    let left = points.iter().map(|(x, _)| *x).min().unwrap_or(0);
+    let top = points.iter().map(|(_, y)| *y).min().unwrap_or(0);
+    let right = points.iter().map(|(x, _)| *x).max().unwrap_or(-1);
+    let bottom = points.iter().map(|(_, y)| *y).max().unwrap_or(-1);
+
+    let mut rows = vec![vec![false; (right - left + 1) as usize]; (bottom - top + 1) as usize];
+    for (x, y) in points {
+        rows[(y - top) as usize][(x - left) as usize] = true;
+    }
+
+    recognize(&rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(rows: &[&str]) -> Vec<Vec<bool>> {
+        rows.iter()
+            .map(|row| row.chars().map(|c| c == '#').collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_splits_glyphs() -> Result<()> {
+        // Any number of blank columns around and between glyphs, and rows
+        // that stop early
+        let rows = bitmap(&[
+            "  #     ####",
+            "  #     #",
+            "  #     ###",
+            "  #     #",
+            "  #     #",
+            "  #### .####",
+        ]);
+        assert_eq!(recognize(&rows)?, "LE");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recognizes_glyphs() -> Result<()> {
+        let rows = [
+            " ###  #...#   #  #",
+            "  #   #...#   #  #",
+            "  #   .#.#.   ####",
+            "  #   ..#..   #  #",
+            "  #   ..#..   #  #",
+            " ###  ..#..   #  #",
+        ];
+        assert_eq!(recognize(&bitmap(&rows))?, "IYH");
+
+        // The same letters in a grid that doesn't start at the origin
+        let mut grid = BTreeMap::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                grid.entry(y as i64 - 3)
+                    .or_insert_with(BTreeMap::new)
+                    .insert(x as i64 + 10, c == '#');
+            }
+        }
+        assert_eq!(recognize_grid(&grid, |pixel| *pixel)?, "IYH");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_unknown_glyphs() {
+        let rows = bitmap(&["#     #", "#     #", "#", "#", "#", "####"]);
+        assert_eq!(
+            recognize(&rows).unwrap_err().to_string(),
+            "Unrecognized glyph(s) #2 at column 6 (read \"L\" from the rest)"
+        );
+
+        let error = recognize(&bitmap(&["#", "#", "", "#", "#", "#", "#"])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Letters are 6 pixels tall, but the image has 7 row(s)"
+        );
+    }
+}
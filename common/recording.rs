@@ -0,0 +1,209 @@
+// Records a VM's inputs and outputs and replays them. Shared by day13 and
+// day15.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{format_err, Context, Error, Result};
+
+use super::intcode::{Program, ProgramState};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Input { instruction: u64, value: i64 },
+    Output { instruction: u64, value: i64 },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Input { instruction, value } => write!(f, "{} in {}", instruction, value),
+            Event::Output { instruction, value } => write!(f, "{} out {}", instruction, value),
+        }
+    }
+}
+
+impl FromStr for Event {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        if parts.len() != 3 {
+            return Err(format_err!("Malformed event \"{}\"", line));
+        }
+
+        let instruction = parts[0]
+            .parse()
+            .with_context(|| format!("Bad instruction count in \"{}\"", line))?;
+        let value = parts[2]
+            .parse()
+            .with_context(|| format!("Bad value in \"{}\"", line))?;
+
+        match parts[1] {
+            "in" => Ok(Event::Input { instruction, value }),
+            "out" => Ok(Event::Output { instruction, value }),
+            kind => Err(format_err!("Unknown event kind \"{}\"", kind)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    pub events: Vec<Event>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self { events: Vec::new() }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    pub fn load(filename: &str) -> Result<Self> {
+        std::fs::read_to_string(filename)
+            .with_context(|| format!("Failed to read recording {}", filename))?
+            .parse()
+    }
+
+    pub fn save(&self, filename: &str) -> Result<()> {
+        std::fs::write(filename, self.to_string())
+            .with_context(|| format!("Failed to write recording {}", filename))
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in self.events.iter() {
+            writeln!(f, "{}", event)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Recording {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut recording = Recording::new();
+
+        for (i, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            recording.push(
+                line.parse()
+                    .with_context(|| format!("Failed to parse line {}", i + 1))?,
+            );
+        }
+
+        Ok(recording)
+    }
+}
+
+fn check_events(expected: &[Event], actual: &[Event], start: usize) -> Result<()> {
+    for (i, event) in actual.iter().enumerate().skip(start) {
+        match expected.get(i) {
+            Some(expected_event) if expected_event == event => {}
+            Some(expected_event) => {
+                return Err(format_err!(
+                    "Diverged at event {}: expected \"{}\", got \"{}\"",
+                    i,
+                    expected_event,
+                    event
+                ));
+            }
+            None => {
+                return Err(format_err!(
+                    "Diverged at event {}: recording ended, got \"{}\"",
+                    i,
+                    event
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Feeds the recorded inputs back into `program`, failing at the first event
+// that doesn't match the recording. Replay stops once the recording runs out.
+pub fn replay(program: &mut Program, recording: &Recording) -> Result<()> {
+    program.start_recording();
+
+    let mut inputs = VecDeque::new();
+    let mut checked = 0;
+    loop {
+        program.run_to_next_input(&mut inputs)?;
+
+        let actual = &program
+            .get_recording()
+            .ok_or(format_err!("Program is not recording"))?
+            .events;
+        check_events(&recording.events, actual, checked)?;
+        checked = actual.len();
+
+        if let ProgramState::Terminated = *program.get_state() {
+            if actual.len() < recording.events.len() {
+                return Err(format_err!(
+                    "Diverged at event {}: program terminated, expected \"{}\"",
+                    actual.len(),
+                    recording.events[actual.len()]
+                ));
+            }
+            break;
+        }
+
+        match recording.events.get(actual.len()) {
+            Some(Event::Input { value, .. }) => inputs.push_back(*value),
+            Some(event) => {
+                return Err(format_err!(
+                    "Diverged at event {}: program is waiting for input, expected \"{}\"",
+                    actual.len(),
+                    event
+                ));
+            }
+            // Interactive programs may be recorded before they terminate
+            None => break,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_flags_first_divergence() -> Result<()> {
+        // Echoes two inputs back, then halts
+        let echo = "3,9,4,9,3,9,4,9,99,0";
+
+        let mut program: Program = echo.parse()?;
+        program.start_recording();
+        program.run(&mut VecDeque::from(vec![5, 7]))?;
+        let recording = program.get_recording().unwrap().clone();
+        assert_eq!(recording.to_string(), "0 in 5\n1 out 5\n2 in 7\n3 out 7\n");
+
+        replay(&mut echo.parse()?, &recording)?;
+
+        let mut changed = recording.clone();
+        changed.events[3] = Event::Output {
+            instruction: 3,
+            value: 8,
+        };
+        assert_eq!(
+            replay(&mut echo.parse()?, &changed)
+                .unwrap_err()
+                .to_string(),
+            "Diverged at event 3: expected \"3 out 8\", got \"3 out 7\""
+        );
+
+        Ok(())
+    }
+}
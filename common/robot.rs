@@ -0,0 +1,301 @@
+// Robots driven by an Intcode brain. Shared by day11 and day15.
+
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, VecDeque};
+
+use anyhow::{format_err, Result};
+use log::trace;
+
+use crate::intcode::Program;
+use crate::point::Point;
+
+// Which way a robot is facing. North is up the screen, so it's negative y.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Heading {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Heading {
+    // Only day15 looks around in every direction
+    #[allow(dead_code)]
+    pub const ALL: [Heading; 4] = [Heading::North, Heading::South, Heading::East, Heading::West];
+
+    pub fn left(self) -> Self {
+        match self {
+            Heading::North => Heading::West,
+            Heading::West => Heading::South,
+            Heading::South => Heading::East,
+            Heading::East => Heading::North,
+        }
+    }
+
+    // Only day11 turns right
+    #[allow(dead_code)]
+    pub fn right(self) -> Self {
+        self.reverse().left()
+    }
+
+    pub fn reverse(self) -> Self {
+        self.left().left()
+    }
+
+    // The point one step away from `point` this way
+    pub fn step(self, point: &Point) -> Point {
+        match self {
+            Heading::North => Point {
+                x: point.x,
+                y: point.y - 1,
+            },
+            Heading::South => Point {
+                x: point.x,
+                y: point.y + 1,
+            },
+            Heading::West => Point {
+                x: point.x - 1,
+                y: point.y,
+            },
+            Heading::East => Point {
+                x: point.x + 1,
+                y: point.y,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pose {
+    pub position: Point,
+    pub heading: Heading,
+}
+
+impl Pose {
+    pub fn new(position: Point, heading: Heading) -> Self {
+        Self { position, heading }
+    }
+}
+
+// Everything a robot has learned about the grid. Points it hasn't seen read
+// as the tile's default.
+#[derive(Clone, Debug)]
+pub struct World<T> {
+    tiles: BTreeMap<Point, T>,
+}
+
+impl<T: Clone + Default> World<T> {
+    pub fn new() -> Self {
+        Self {
+            tiles: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, point: &Point) -> T {
+        self.tiles.get(point).cloned().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, point: &Point, tile: T) {
+        self.tiles.insert(point.clone(), tile);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Point, &T)> {
+        self.tiles.iter()
+    }
+
+    // Top left and bottom right corners of everything set so far
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let mut points = self.tiles.keys();
+        let first = points.next()?;
+
+        let mut top_left = first.clone();
+        let mut bottom_right = first.clone();
+        for point in points {
+            top_left.x = min(top_left.x, point.x);
+            top_left.y = min(top_left.y, point.y);
+            bottom_right.x = max(bottom_right.x, point.x);
+            bottom_right.y = max(bottom_right.y, point.y);
+        }
+
+        Some((top_left, bottom_right))
+    }
+
+    // One line per row within the bounds, with `draw` picking each character
+    pub fn render(&self, draw: impl Fn(&Point, T) -> char) -> String {
+        let (top_left, bottom_right) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return String::new(),
+        };
+
+        let mut rows = Vec::new();
+        for y in top_left.y..bottom_right.y + 1 {
+            let mut row = String::new();
+            for x in top_left.x..bottom_right.x + 1 {
+                let point = Point { x, y };
+                let tile = self.get(&point);
+                row.push(draw(&point, tile));
+            }
+            rows.push(row);
+        }
+
+        rows.join("\n")
+    }
+}
+
+// How a particular robot talks to its brain. Each step the robot sends the
+// encoded command, reads RESPONSE_LEN outputs back and lets the protocol
+// move it and update what it knows about the world.
+pub trait Protocol {
+    type Command;
+    type Response;
+    type Tile: Clone + Default;
+
+    const RESPONSE_LEN: usize;
+
+    fn encode(&self, command: &Self::Command, pose: &Pose, world: &World<Self::Tile>) -> Vec<i64>;
+
+    fn decode(&self, outputs: &[i64]) -> Result<Self::Response>;
+
+    fn apply(
+        &self,
+        command: &Self::Command,
+        response: &Self::Response,
+        pose: &mut Pose,
+        world: &mut World<Self::Tile>,
+    ) -> Result<()>;
+}
+
+pub struct Robot<P: Protocol> {
+    pub brain: Program,
+    pub pose: Pose,
+    pub world: World<P::Tile>,
+    protocol: P,
+    inputs: VecDeque<i64>,
+    trajectory: Vec<Pose>,
+}
+
+impl<P: Protocol> Robot<P> {
+    pub fn new(brain: Program, protocol: P, start: Pose) -> Self {
+        Self {
+            brain,
+            pose: start.clone(),
+            world: World::new(),
+            protocol,
+            inputs: VecDeque::new(),
+            trajectory: vec![start],
+        }
+    }
+
+    // Returns None once the brain halts instead of responding
+    pub fn step(&mut self, command: &P::Command) -> Result<Option<P::Response>> {
+        let encoded = self.protocol.encode(command, &self.pose, &self.world);
+        self.inputs.extend(encoded);
+
+        let mut outputs = Vec::with_capacity(P::RESPONSE_LEN);
+        while outputs.len() < P::RESPONSE_LEN {
+            match self.brain.run_to_next_output(&mut self.inputs)? {
+                Some(output) => outputs.push(output),
+                None if outputs.is_empty() => {
+                    // A halted brain never reads the command
+                    self.inputs.clear();
+                    return Ok(None);
+                }
+                None => {
+                    return Err(format_err!(
+                        "Brain halted after {} of {} response value(s)",
+                        outputs.len(),
+                        P::RESPONSE_LEN
+                    ));
+                }
+            }
+        }
+
+        let response = self.protocol.decode(&outputs)?;
+        self.protocol
+            .apply(command, &response, &mut self.pose, &mut self.world)?;
+        trace!("Robot now at {:?}", self.pose);
+
+        if self.trajectory.last() != Some(&self.pose) {
+            self.trajectory.push(self.pose.clone());
+        }
+
+        Ok(Some(response))
+    }
+
+    // Every pose the robot has been in, starting with where it began
+    pub fn trajectory(&self) -> &[Pose] {
+        &self.trajectory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::str::FromStr;
+
+    // Sends a heading and expects it echoed back, moving when it is
+    struct Echo;
+
+    impl Protocol for Echo {
+        type Command = Heading;
+        type Response = i64;
+        type Tile = bool;
+
+        const RESPONSE_LEN: usize = 1;
+
+        fn encode(&self, command: &Heading, _: &Pose, _: &World<bool>) -> Vec<i64> {
+            vec![*command as i64]
+        }
+
+        fn decode(&self, outputs: &[i64]) -> Result<i64> {
+            Ok(outputs[0])
+        }
+
+        fn apply(
+            &self,
+            command: &Heading,
+            response: &i64,
+            pose: &mut Pose,
+            world: &mut World<bool>,
+        ) -> Result<()> {
+            if *response != *command as i64 {
+                return Err(format_err!("Expected {:?} back, got {}", command, response));
+            }
+
+            pose.heading = *command;
+            pose.position = command.step(&pose.position);
+            world.set(&pose.position, true);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_robot() -> Result<()> {
+        // Echoes one input, then halts
+        let brain = Program::from_str("3,5,4,5,99,0")?;
+        let mut robot = Robot::new(brain, Echo, Pose::new(Point { x: 0, y: 0 }, Heading::North));
+
+        assert_eq!(robot.step(&Heading::East)?, Some(Heading::East as i64));
+        assert_eq!(robot.step(&Heading::South)?, None);
+        assert!(robot.inputs.is_empty());
+
+        assert_eq!(
+            robot.trajectory(),
+            &[
+                Pose::new(Point { x: 0, y: 0 }, Heading::North),
+                Pose::new(Point { x: 1, y: 0 }, Heading::East),
+            ]
+        );
+        assert_eq!(
+            robot.world.render(|_, seen| if seen { '#' } else { '.' }),
+            "#"
+        );
+        assert_eq!(Heading::West.reverse(), Heading::East);
+        assert_eq!(Heading::North.right(), Heading::East);
+        assert_eq!(Heading::North.right().left(), Heading::North);
+
+        Ok(())
+    }
+}
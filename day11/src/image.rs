@@ -0,0 +1,163 @@
+use crate::point::Point;
+use crate::robot::World;
+
+// A black and white picture of part of a world, one pixel per point
+#[derive(Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<bool>,
+}
+
+impl Image {
+    // Covers everything the world has seen, with `lit` picking white pixels
+    pub fn from_world<T: Clone + Default>(world: &World<T>, lit: impl Fn(T) -> bool) -> Self {
+        let (top_left, bottom_right) = match world.bounds() {
+            Some(bounds) => bounds,
+            None => {
+                return Self {
+                    width: 0,
+                    height: 0,
+                    pixels: Vec::new(),
+                }
+            }
+        };
+
+        let mut pixels = Vec::new();
+        for y in top_left.y..bottom_right.y + 1 {
+            for x in top_left.x..bottom_right.x + 1 {
+                pixels.push(lit(world.get(&Point { x, y })));
+            }
+        }
+
+        Self {
+            width: (bottom_right.x - top_left.x + 1) as usize,
+            height: (bottom_right.y - top_left.y + 1) as usize,
+            pixels,
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+
+    // Plain (P3) PPM, with each pixel blown up to a `scale` sized square
+    pub fn to_ppm(&self, scale: usize) -> String {
+        let mut lines = vec![
+            "P3".to_string(),
+            format!("{} {}", self.width * scale, self.height * scale),
+            "255".to_string(),
+        ];
+
+        for y in 0..self.height * scale {
+            let row: Vec<&str> = (0..self.width * scale)
+                .map(|x| {
+                    if self.get(x / scale, y / scale) {
+                        "255 255 255"
+                    } else {
+                        "0 0 0"
+                    }
+                })
+                .collect();
+            lines.push(row.join(" "));
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    // A black background with a `scale` sized square for each white pixel
+    pub fn to_svg(&self, scale: usize) -> String {
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut lines = vec![
+            format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+                 viewBox=\"0 0 {} {}\">",
+                width, height, width, height
+            ),
+            format!(
+                "  <rect width=\"{}\" height=\"{}\" fill=\"black\"/>",
+                width, height
+            ),
+        ];
+
+        for y in 0..self.height {
+            for x in (0..self.width).filter(|x| self.get(*x, y)) {
+                lines.push(format!(
+                    "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"white\"/>",
+                    x * scale,
+                    y * scale,
+                    scale,
+                    scale
+                ));
+            }
+        }
+        lines.push("</svg>".to_string());
+
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Lit at the top left, dark everywhere else
+    fn corner() -> Image {
+        Image {
+            width: 2,
+            height: 2,
+            pixels: vec![true, false, false, false],
+        }
+    }
+
+    #[test]
+    fn test_from_world() {
+        let mut world = World::new();
+        world.set(&Point { x: -1, y: 2 }, true);
+        world.set(&Point { x: 0, y: 3 }, false);
+
+        assert_eq!(Image::from_world(&world, |lit| lit), corner());
+    }
+
+    #[test]
+    fn test_from_empty_world() {
+        let image = Image::from_world(&World::<bool>::new(), |lit| lit);
+
+        assert_eq!((image.width, image.height), (0, 0));
+        assert_eq!(image.to_ppm(3), "P3\n0 0\n255\n");
+    }
+
+    #[test]
+    fn test_get() {
+        assert!(corner().get(0, 0));
+        assert!(!corner().get(1, 1));
+        assert!(!corner().get(2, 0));
+    }
+
+    #[test]
+    fn test_ppm() {
+        assert_eq!(
+            corner().to_ppm(1),
+            "P3\n2 2\n255\n255 255 255 0 0 0\n0 0 0 0 0 0\n"
+        );
+
+        // Each pixel becomes a 2x2 block
+        let ppm = corner().to_ppm(2);
+        let rows: Vec<&str> = ppm.lines().skip(3).collect();
+        assert_eq!(ppm.lines().nth(1), Some("4 4"));
+        assert_eq!(rows[1], "255 255 255 255 255 255 0 0 0 0 0 0");
+        assert_eq!(rows[2], "0 0 0 0 0 0 0 0 0 0 0 0");
+    }
+
+    #[test]
+    fn test_svg() {
+        assert_eq!(
+            corner().to_svg(10),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"20\" height=\"20\" \
+             viewBox=\"0 0 20 20\">\n  \
+             <rect width=\"20\" height=\"20\" fill=\"black\"/>\n  \
+             <rect x=\"0\" y=\"0\" width=\"10\" height=\"10\" fill=\"white\"/>\n\
+             </svg>\n"
+        );
+    }
+}
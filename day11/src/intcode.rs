@@ -1,9 +1,9 @@
 use std::collections::{BTreeMap, VecDeque};
-use std::num::ParseIntError;
 use std::convert::{TryFrom, TryInto};
 use std::str::FromStr;
 
 use anyhow::{format_err, Context, Error, Result};
+use intcode_tape::parse_values;
 use log::{debug, info, trace};
 
 #[derive(Debug)]
@@ -64,12 +64,12 @@ impl TryFrom<&str> for OpCode {
 
 #[derive(Clone)]
 pub struct Tape {
-    memory: BTreeMap<usize, i64>,
+    pub(crate) memory: BTreeMap<usize, i64>,
     relative_base: i64,
 }
 
 impl Tape {
-    fn new(program: &Vec<i64>) -> Self {
+    pub(crate) fn new(program: &[i64]) -> Self {
         let mut tape = Tape {
             memory: BTreeMap::new(),
             relative_base: 0,
@@ -97,22 +97,16 @@ impl Tape {
         self.relative_base
     }
 
-    fn set_relative_base(&mut self, new_base: i64) {
+    pub(crate) fn set_relative_base(&mut self, new_base: i64) {
         self.relative_base = new_base;
     }
 }
 
 impl FromStr for Tape {
-    type Err = ParseIntError;
+    type Err = Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut ret = Vec::new();
-
-        for num in input.trim().split(",") {
-            ret.push(num.parse()?);
-        }
-
-        Ok(Tape::new(&ret))
+        Ok(Tape::new(&parse_values(input)?))
     }
 }
 
@@ -136,6 +130,10 @@ impl TryFrom<char> for FetchMode {
     }
 }
 
+fn to_address(value: i64) -> Result<usize> {
+    usize::try_from(value).map_err(|_| format_err!("Negative address {}", value))
+}
+
 #[derive(Debug)]
 struct Argument {
     mode: FetchMode,
@@ -146,28 +144,34 @@ impl Argument {
     fn get(&self, tape: &Tape, relative_base: i64) -> Option<i64> {
         match self.mode {
             FetchMode::Immediate => Some(self.value),
-            FetchMode::Position => tape.get(self.value as usize),
-            FetchMode::Relative => tape.get((self.value + relative_base) as usize),
+            FetchMode::Position => to_address(self.value)
+                .ok()
+                .and_then(|address| tape.get(address)),
+            FetchMode::Relative => self
+                .value
+                .checked_add(relative_base)
+                .and_then(|address| to_address(address).ok())
+                .and_then(|address| tape.get(address)),
         }
     }
 
-    fn get_for_set(&self, relative_base: i64) -> i64 {
+    fn get_for_set(&self, relative_base: i64) -> Option<i64> {
         match self.mode {
-            FetchMode::Relative => self.value + relative_base,
-            _ => self.value,
+            FetchMode::Relative => self.value.checked_add(relative_base),
+            _ => Some(self.value),
         }
     }
 }
 
 #[derive(Debug)]
-struct Instruction {
+pub(crate) struct Instruction {
     position: usize,
     opcode: OpCode,
     arguments: Vec<Argument>,
 }
 
 impl Instruction {
-    fn new(tape: &Tape, offset: usize) -> Result<Self> {
+    pub(crate) fn new(tape: &Tape, offset: usize) -> Result<Self> {
         let code = format!(
             "{:0>2}",
             tape.get(offset)
@@ -193,7 +197,11 @@ impl Instruction {
                 mode: c
                     .try_into()
                     .with_context(|| format!("Failed to parse mode \"{}\"", c))?,
-                value: tape.get(offset + i + 1).unwrap(),
+                value: tape.get(offset + i + 1).ok_or(format_err!(
+                    "Missing argument {} for instruction at offset {}",
+                    i + 1,
+                    offset
+                ))?,
             })
         }
 
@@ -242,13 +250,19 @@ impl Instruction {
             ))
     }
 
-    fn get_argument_value_for_set(&self, tape: &Tape, index: usize) -> Result<i64> {
-        Ok(self
+    fn get_argument_value_for_set(&self, tape: &Tape, index: usize) -> Result<usize> {
+        let address = self
             .get_argument(index)?
-            .get_for_set(tape.get_relative_base()))
+            .get_for_set(tape.get_relative_base())
+            .ok_or(format_err!(
+                "Argument {} for opcode {:?} overflowed",
+                index + 1,
+                self.opcode
+            ))?;
+        to_address(address)
     }
 
-    fn run(
+    pub(crate) fn run(
         &self,
         tape: &mut Tape,
         inputs: &mut VecDeque<i64>,
@@ -261,7 +275,9 @@ impl Instruction {
                 let arg1 = self.get_argument_value(tape, 0)?;
                 let arg2 = self.get_argument_value(tape, 1)?;
                 let result_offset = self.get_argument_value_for_set(tape, 2)?;
-                let result = arg1 + arg2;
+                let result = arg1
+                    .checked_add(arg2)
+                    .ok_or(format_err!("Overflow adding {} and {}", arg1, arg2))?;
 
                 trace!(
                     "[ADD] {} + {} = {}, [{}]",
@@ -271,7 +287,7 @@ impl Instruction {
                     result_offset
                 );
 
-                tape.set(result_offset as usize, result).with_context(|| {
+                tape.set(result_offset, result).with_context(|| {
                     format!(
                         "Failed to set multiplied value {} to tape index {}",
                         result, result_offset
@@ -288,7 +304,9 @@ impl Instruction {
                 let arg2 = self.get_argument_value(tape, 1)?;
                 let result_offset = self.get_argument_value_for_set(tape, 2)?;
 
-                let result = arg1 * arg2;
+                let result = arg1
+                    .checked_mul(arg2)
+                    .ok_or(format_err!("Overflow multiplying {} and {}", arg1, arg2))?;
 
                 trace!(
                     "[MUL] {} * {} = {}, [{}]",
@@ -298,7 +316,7 @@ impl Instruction {
                     result_offset
                 );
 
-                tape.set(result_offset as usize, result).with_context(|| {
+                tape.set(result_offset, result).with_context(|| {
                     format!(
                         "Failed to set multiplied value {} to tape index {}",
                         result, result_offset
@@ -319,7 +337,7 @@ impl Instruction {
                 trace!("[INP] {} -> [{}]", value, result_offset);
                 info!("[INP] {} -> [{}]", value, result_offset);
 
-                tape.set(result_offset as usize, value).with_context(|| {
+                tape.set(result_offset, value).with_context(|| {
                     format!(
                         "Failed to set input value {} to tape index {}",
                         value, result_offset
@@ -347,7 +365,7 @@ impl Instruction {
                     next_offset: if arg1 == 0 {
                         default_next_offset
                     } else {
-                        arg2 as usize
+                        to_address(arg2)?
                     },
                     relative_base: tape.get_relative_base(),
                 })
@@ -358,7 +376,7 @@ impl Instruction {
 
                 Ok(InstructionResult::Continue {
                     next_offset: if arg1 == 0 {
-                        arg2 as usize
+                        to_address(arg2)?
                     } else {
                         default_next_offset
                     },
@@ -372,7 +390,7 @@ impl Instruction {
 
                 let value = if arg1 < arg2 { 1 } else { 0 };
 
-                tape.set(result_offset as usize, value).with_context(|| {
+                tape.set(result_offset, value).with_context(|| {
                     format!(
                         "Failed to set less than value {} to tape index {}",
                         value, result_offset
@@ -391,7 +409,7 @@ impl Instruction {
 
                 let value = if arg1 == arg2 { 1 } else { 0 };
 
-                tape.set(result_offset as usize, value).with_context(|| {
+                tape.set(result_offset, value).with_context(|| {
                     format!(
                         "Failed to set less than value {} to tape index {}",
                         value, result_offset
@@ -405,10 +423,17 @@ impl Instruction {
             }
             OpCode::AdjustRelativeBase => {
                 let arg = self.get_argument_value(tape, 0)?;
-                let relative_base = tape.get_relative_base() + arg;
+                let relative_base = tape
+                    .get_relative_base()
+                    .checked_add(arg)
+                    .ok_or(format_err!(
+                        "Overflow adjusting relative base {} by {}",
+                        tape.get_relative_base(),
+                        arg
+                    ))?;
                 Ok(InstructionResult::Continue {
                     next_offset: default_next_offset,
-                    relative_base: relative_base,
+                    relative_base,
                 })
             }
             OpCode::Terminate => Ok(InstructionResult::Terminate),
@@ -416,7 +441,7 @@ impl Instruction {
     }
 }
 
-enum InstructionResult {
+pub(crate) enum InstructionResult {
     Continue {
         next_offset: usize,
         relative_base: i64,
@@ -470,44 +495,12 @@ impl Program {
 
         Ok(outputs.back().cloned())
     }
-
-    pub fn run(&mut self, inputs: &mut VecDeque<i64>) -> Result<VecDeque<i64>> {
-        // TODO(jsvana): make this not duplicated
-        let mut outputs = VecDeque::new();
-
-        loop {
-            let instruction = Instruction::new(&self.tape, self.pc)
-                .with_context(|| format!("Failed to build instruction at offset {}", self.pc))?;
-
-            match instruction
-                .run(&mut self.tape, inputs, &mut outputs)
-                .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?
-            {
-                InstructionResult::Continue {
-                    next_offset,
-                    relative_base,
-                } => {
-                    self.pc = next_offset;
-                    self.tape.set_relative_base(relative_base);
-                }
-                InstructionResult::Terminate => break,
-            }
-        }
-
-        Ok(outputs)
-    }
 }
 
 impl FromStr for Program {
-    type Err = ParseIntError;
+    type Err = Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut ret = Vec::new();
-
-        for num in input.trim().split(",") {
-            ret.push(num.parse()?);
-        }
-
-        Ok(Program::new(&Tape::new(&ret)))
+        Ok(Program::new(&input.parse()?))
     }
 }
@@ -1,156 +1,230 @@
+mod image;
 mod intcode;
-mod map;
+#[path = "../../common/ocr.rs"]
+mod ocr;
 mod point;
+#[path = "../../common/robot.rs"]
+mod robot;
 
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet};
 use std::str::FromStr;
 
-use anyhow::{format_err, Result};
-use log::{debug, info, trace};
+use anyhow::{format_err, Context, Result};
+use log::info;
 
-use crate::intcode::Program;
-use crate::map::Map;
+use crate::image::Image;
+use crate::intcode::{Program, Tape};
 use crate::point::Point;
+use crate::robot::{Heading, Pose, Protocol, Robot, World};
 
-enum Direction {
-    Up,
-    Down,
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Color {
+    #[default]
+    Black,
+    White,
+}
+
+enum Turn {
     Left,
     Right,
 }
 
-struct Robot {
-    position: Point,
-    direction: Direction,
-    brain: Program,
+struct Paint {
+    color: Color,
+    turn: Turn,
 }
 
-enum RobotState {
-    Running,
-    Done,
-}
+// The hull painting robot sends the color of the panel it's over and gets
+// back a color to paint it and which way to turn before moving forward
+struct Painter;
 
-impl Robot {
-    fn new(brain: Program) -> Self {
-        Robot {
-            position: Point { x: 0, y: 0 },
-            direction: Direction::Up,
-            brain,
-        }
-    }
+impl Protocol for Painter {
+    type Command = ();
+    type Response = Paint;
+    type Tile = Color;
 
-    fn turn_left(&mut self) {
-        self.direction = match self.direction {
-            Direction::Up => Direction::Left,
-            Direction::Left => Direction::Down,
-            Direction::Down => Direction::Right,
-            Direction::Right => Direction::Up,
-        }
-    }
+    const RESPONSE_LEN: usize = 2;
 
-    fn turn_right(&mut self) {
-        self.direction = match self.direction {
-            Direction::Up => Direction::Right,
-            Direction::Right => Direction::Down,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
-        }
+    fn encode(&self, _: &(), pose: &Pose, world: &World<Color>) -> Vec<i64> {
+        vec![match world.get(&pose.position) {
+            Color::Black => 0,
+            Color::White => 1,
+        }]
     }
 
-    fn move_forward(&mut self) {
-        match self.direction {
-            Direction::Up => self.position.y -= 1,
-            Direction::Left => self.position.x -= 1,
-            Direction::Down => self.position.y += 1,
-            Direction::Right => self.position.x += 1,
-        }
+    fn decode(&self, outputs: &[i64]) -> Result<Paint> {
+        let color = match outputs[0] {
+            0 => Color::Black,
+            1 => Color::White,
+            color => return Err(format_err!("Unknown color code \"{}\"", color)),
+        };
+        let turn = match outputs[1] {
+            0 => Turn::Left,
+            1 => Turn::Right,
+            turn => return Err(format_err!("Unknown turn code \"{}\"", turn)),
+        };
+
+        Ok(Paint { color, turn })
     }
 
-    fn color_to_input(&self, map: &Map) -> Result<i64> {
-        let color = map.get(&self.position).unwrap();
-        match color {
-            // '*' for painted black, '.' for not yet painted
-            '.' | '*' => Ok(0),
-            '#' => Ok(1),
-            _ => Err(format_err!("Unknown color \"{}\"", color)),
-        }
+    fn apply(&self, _: &(), paint: &Paint, pose: &mut Pose, world: &mut World<Color>) -> Result<()> {
+        world.set(&pose.position, paint.color);
+
+        pose.heading = match paint.turn {
+            Turn::Left => pose.heading.left(),
+            Turn::Right => pose.heading.right(),
+        };
+        pose.position = pose.heading.step(&pose.position);
+
+        Ok(())
     }
+}
+
+impl FromStr for Color {
+    type Err = anyhow::Error;
 
-    fn step(&mut self, map: &mut Map) -> Result<RobotState> {
-        let mut inputs = VecDeque::new();
-        inputs.push_back(self.color_to_input(map)?);
-
-        match self.brain.run_to_next_output(&mut inputs)? {
-            Some(color) => {
-                map.set_to_char(
-                    &self.position,
-                    match color {
-                        0 => '*',
-                        1 => '#',
-                        _ => {
-                            return Err(format_err!("Unknown color code \"{}\"", color));
-                        }
-                    },
-                );
-            }
-            None => {
-                return Ok(RobotState::Done);
-            }
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "black" => Ok(Color::Black),
+            "white" => Ok(Color::White),
+            _ => Err(format_err!("Unknown color \"{}\" (expected black or white)", input)),
         }
+    }
+}
 
-        let turn = self.brain.run_to_next_output(&mut inputs)?.unwrap();
+// Runs a fresh robot until its brain halts, returning it along with every
+// panel it painted at least once. Starting on white colors the first panel
+// without painting it.
+fn paint(tape: &Tape, start: Color) -> Result<(Robot<Painter>, BTreeSet<Point>)> {
+    let mut robot = Robot::new(
+        Program::new(tape),
+        Painter,
+        Pose::new(Point { x: 0, y: 0 }, Heading::North),
+    );
+    if start == Color::White {
+        robot.world.set(&robot.pose.position.clone(), Color::White);
+    }
 
-        match turn {
-            0 => self.turn_left(),
-            1 => self.turn_right(),
-            _ => {
-                return Err(format_err!("Unknown turn code \"{}\"", turn));
-            }
+    // Each response paints the panel the robot was on before it moved
+    let mut painted = BTreeSet::new();
+    loop {
+        let position = robot.pose.position.clone();
+        if robot.step(&())?.is_none() {
+            break;
         }
+        painted.insert(position);
+    }
 
-        self.move_forward();
+    info!(
+        "Robot starting on {:?} went through {} pose(s)",
+        start,
+        robot.trajectory().len()
+    );
 
-        Ok(RobotState::Running)
-    }
+    Ok((robot, painted))
 }
 
-fn count_painted_points(map: &Map) -> u64 {
-    let mut total = 0;
-    for row in map.data.values() {
-        for ch in row.values() {
-            if *ch == '*' || *ch == '#' {
-                total += 1;
-            }
-        }
+// The world as rows keyed by y, then x
+fn grid(world: &World<Color>) -> BTreeMap<i64, BTreeMap<i64, Color>> {
+    let mut grid = BTreeMap::new();
+    for (point, color) in world.iter() {
+        grid.entry(i64::from(point.y))
+            .or_insert_with(BTreeMap::new)
+            .insert(i64::from(point.x), *color);
     }
 
-    total
+    grid
 }
 
+include!("../../common/args.rs");
+
 fn main() -> Result<()> {
-    env_logger::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let filename = flag_value("--program").unwrap_or_else(|| "input.txt".to_string());
+    let tape = Tape::from_str(
+        &std::fs::read_to_string(&filename)
+            .with_context(|| format!("Failed to read brain from {}", filename))?,
+    )?;
+    let start = match flag_value("--start") {
+        Some(color) => color.parse()?,
+        None => Color::White,
+    };
+    let scale = match flag_value("--scale") {
+        Some(scale) => scale.parse()?,
+        None => 10,
+    };
+
+    for color in [Color::Black, Color::White].iter() {
+        let (_, painted) = paint(&tape, *color)?;
+        println!("Total painted starting on {:?}: {}", color, painted.len());
+    }
 
-    let brain = Program::from_str("3,8,1005,8,318,1106,0,11,0,0,0,104,1,104,0,3,8,1002,8,-1,10,1001,10,1,10,4,10,108,1,8,10,4,10,1002,8,1,28,1,107,14,10,1,107,18,10,3,8,102,-1,8,10,101,1,10,10,4,10,108,1,8,10,4,10,102,1,8,58,1006,0,90,2,1006,20,10,3,8,1002,8,-1,10,101,1,10,10,4,10,1008,8,1,10,4,10,1001,8,0,88,2,103,2,10,2,4,7,10,3,8,1002,8,-1,10,101,1,10,10,4,10,1008,8,1,10,4,10,1001,8,0,118,1,1009,14,10,1,1103,9,10,3,8,1002,8,-1,10,1001,10,1,10,4,10,108,0,8,10,4,10,1002,8,1,147,1006,0,59,1,104,4,10,2,106,18,10,3,8,102,-1,8,10,1001,10,1,10,4,10,1008,8,0,10,4,10,101,0,8,181,2,4,17,10,1006,0,36,1,107,7,10,2,1008,0,10,3,8,1002,8,-1,10,1001,10,1,10,4,10,108,0,8,10,4,10,101,0,8,217,3,8,102,-1,8,10,1001,10,1,10,4,10,1008,8,0,10,4,10,101,0,8,240,1006,0,64,3,8,102,-1,8,10,1001,10,1,10,4,10,108,0,8,10,4,10,1002,8,1,264,3,8,1002,8,-1,10,1001,10,1,10,4,10,1008,8,1,10,4,10,1001,8,0,287,1,1104,15,10,1,102,8,10,1006,0,2,101,1,9,9,1007,9,940,10,1005,10,15,99,109,640,104,0,104,1,21102,932700857236,1,1,21101,335,0,0,1106,0,439,21101,0,387511792424,1,21101,346,0,0,1106,0,439,3,10,104,0,104,1,3,10,104,0,104,0,3,10,104,0,104,1,3,10,104,0,104,1,3,10,104,0,104,0,3,10,104,0,104,1,21101,46372252675,0,1,21102,393,1,0,1106,0,439,21101,97806162983,0,1,21102,404,1,0,1105,1,439,3,10,104,0,104,0,3,10,104,0,104,0,21102,1,825452438376,1,21101,0,427,0,1106,0,439,21102,709475586836,1,1,21101,0,438,0,1106,0,439,99,109,2,22101,0,-1,1,21101,40,0,2,21102,1,470,3,21102,1,460,0,1106,0,503,109,-2,2106,0,0,0,1,0,0,1,109,2,3,10,204,-1,1001,465,466,481,4,0,1001,465,1,465,108,4,465,10,1006,10,497,1101,0,0,465,109,-2,2105,1,0,0,109,4,2102,1,-1,502,1207,-3,0,10,1006,10,520,21102,1,0,-3,21202,-3,1,1,21202,-2,1,2,21101,0,1,3,21101,0,539,0,1106,0,544,109,-4,2105,1,0,109,5,1207,-3,1,10,1006,10,567,2207,-4,-2,10,1006,10,567,22101,0,-4,-4,1106,0,635,21202,-4,1,1,21201,-3,-1,2,21202,-2,2,3,21102,586,1,0,1105,1,544,22101,0,1,-4,21102,1,1,-1,2207,-4,-2,10,1006,10,605,21102,0,1,-1,22202,-2,-1,-2,2107,0,-3,10,1006,10,627,22101,0,-1,1,21102,1,627,0,106,0,502,21202,-2,-1,-2,22201,-4,-2,-4,109,-5,2105,1,0")?;
-    let mut map = Map::new();
+    let (robot, _) = paint(&tape, start)?;
 
-    let mut robot = Robot::new(brain);
+    println!(
+        "{}",
+        robot.world.render(|_, color| match color {
+            Color::White => '#',
+            Color::Black => ' ',
+        })
+    );
 
-    map.set_to_char(&robot.position, '#');
+    let image = Image::from_world(&robot.world, |color| color == Color::White);
 
-    let mut running = true;
+    if let Some(filename) = flag_value("--ppm") {
+        std::fs::write(&filename, image.to_ppm(scale))
+            .with_context(|| format!("Failed to write {}", filename))?;
+    }
+    if let Some(filename) = flag_value("--svg") {
+        std::fs::write(&filename, image.to_svg(scale))
+            .with_context(|| format!("Failed to write {}", filename))?;
+    }
 
-    while running {
-        running = match robot.step(&mut map)? {
-            RobotState::Running => true,
-            RobotState::Done => false,
+    // Read after exporting, so a hull that can't be read can still be looked at
+    let registration = ocr::recognize_grid(&grid(&robot.world), |color| *color == Color::White)
+        .context("Unable to read the hull")?;
+    println!("Registration identifier: {}", registration);
+
+    // Lets a run double as a check on the painter
+    if let Some(expected) = flag_value("--expect") {
+        if registration != expected {
+            return Err(format_err!(
+                "Expected \"{}\", read \"{}\"",
+                expected,
+                registration
+            ));
         }
     }
 
-    //println!("Map: {:?}", map);
+    Ok(())
+}
 
-    println!("Total painted: {}", count_painted_points(&map));
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    map.print();
+    #[test]
+    fn test_start_panel_is_not_painted() -> Result<()> {
+        // Halts before painting anything
+        let (robot, painted) = paint(&Tape::from_str("99")?, Color::White)?;
+        assert!(painted.is_empty());
+        assert_eq!(robot.world.get(&Point { x: 0, y: 0 }), Color::White);
 
-    Ok(())
+        Ok(())
+    }
+
+    #[test]
+    fn test_counts_painted_panels() -> Result<()> {
+        // Paints its panel black and turns right, twice, then halts
+        let brain = Tape::from_str("3,0,104,0,104,1,3,0,104,0,104,1,99")?;
+        let (robot, painted) = paint(&brain, Color::White)?;
+
+        let start = Point { x: 0, y: 0 };
+        let next = Point { x: 1, y: 0 };
+        assert_eq!(painted, vec![start.clone(), next].into_iter().collect());
+        assert_eq!(robot.world.get(&start), Color::Black);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,172 @@
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+
+use anyhow::{format_err, Error, Result};
+
+// What an arcade program asks the screen to do. Drawing at (-1, 0) sets the
+// score display instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event<T> {
+    DrawTile { x: i64, y: i64, tile: T },
+    SetScore(i64),
+}
+
+// Turns arcade output into events, three values at a time. A batch of output
+// doesn't have to end on a whole triple; leftovers wait for the next batch.
+pub struct Decoder<T> {
+    pending: Vec<i64>,
+    bounds: Option<(i64, i64)>,
+    tile: PhantomData<T>,
+}
+
+impl<T> Decoder<T>
+where
+    T: TryFrom<i64>,
+    T::Error: Into<Error>,
+{
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            bounds: None,
+            tile: PhantomData,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_bounds(width: i64, height: i64) -> Self {
+        Self {
+            bounds: Some((width, height)),
+            ..Self::new()
+        }
+    }
+
+    // From now on also rejects tiles drawn at or past `width` and `height`.
+    // Values waiting on the rest of their triple are kept.
+    pub fn set_bounds(&mut self, width: i64, height: i64) {
+        self.bounds = Some((width, height));
+    }
+
+    fn decode(&self, x: i64, y: i64, value: i64) -> Result<Event<T>> {
+        if x == -1 && y == 0 {
+            return Ok(Event::SetScore(value));
+        }
+
+        let outside = match self.bounds {
+            Some((width, height)) => x >= width || y >= height,
+            None => false,
+        };
+        if x < 0 || y < 0 || outside {
+            return Err(format_err!(
+                "Tile {} drawn off screen at ({}, {})",
+                value,
+                x,
+                y
+            ));
+        }
+
+        let tile = T::try_from(value)
+            .map_err(|e| e.into().context(format!("Bad tile at ({}, {})", x, y)))?;
+
+        Ok(Event::DrawTile { x, y, tile })
+    }
+
+    pub fn feed(&mut self, outputs: impl IntoIterator<Item = i64>) -> Result<Vec<Event<T>>> {
+        self.pending.extend(outputs);
+
+        let whole = self.pending.len() - self.pending.len() % 3;
+        let values: Vec<i64> = self.pending.drain(..whole).collect();
+
+        values
+            .chunks(3)
+            .map(|triple| self.decode(triple[0], triple[1], triple[2]))
+            .collect()
+    }
+
+    // Values still waiting on the rest of their triple
+    #[cfg(test)]
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Checks the program didn't stop partway through a triple
+    pub fn finish(&self) -> Result<()> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            Err(format_err!(
+                "Output ended partway through a triple ({:?})",
+                self.pending
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Tile;
+
+    #[test]
+    fn test_buffers_partial_triples() -> Result<()> {
+        let mut decoder: Decoder<Tile> = Decoder::new();
+
+        assert_eq!(
+            decoder.feed(vec![1, 2, 3, -1, 0])?,
+            vec![Event::DrawTile {
+                x: 1,
+                y: 2,
+                tile: Tile::Paddle
+            }]
+        );
+        assert_eq!(decoder.pending(), 2);
+        assert_eq!(
+            decoder.finish().unwrap_err().to_string(),
+            "Output ended partway through a triple ([-1, 0])"
+        );
+
+        assert_eq!(decoder.feed(vec![42])?, vec![Event::SetScore(42)]);
+        assert_eq!(decoder.pending(), 0);
+        assert!(decoder.finish().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_negative_positions() {
+        let mut decoder: Decoder<Tile> = Decoder::new();
+
+        // Only (-1, 0) is the score
+        assert_eq!(
+            decoder.feed(vec![-1, 1, 0]).unwrap_err().to_string(),
+            "Tile 0 drawn off screen at (-1, 1)"
+        );
+    }
+
+    #[test]
+    fn test_rejects_out_of_bounds() -> Result<()> {
+        let mut decoder: Decoder<Tile> = Decoder::with_bounds(10, 10);
+
+        assert_eq!(decoder.feed(vec![9, 9, 0])?.len(), 1);
+        assert_eq!(
+            decoder.feed(vec![3, 10, 0]).unwrap_err().to_string(),
+            "Tile 0 drawn off screen at (3, 10)"
+        );
+        assert_eq!(
+            decoder.feed(vec![10, 3, 0]).unwrap_err().to_string(),
+            "Tile 0 drawn off screen at (10, 3)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_bad_tile() {
+        let mut decoder: Decoder<Tile> = Decoder::new();
+
+        assert_eq!(
+            decoder.feed(vec![3, 4, 9]).unwrap_err().to_string(),
+            "Bad tile at (3, 4)"
+        );
+    }
+}
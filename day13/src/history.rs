@@ -0,0 +1,80 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use super::intcode::Tape;
+
+#[derive(Clone, Debug)]
+pub struct Write {
+    pub address: usize,
+    pub old: Option<i64>,
+    pub new: i64,
+}
+
+// Everything needed to undo a single instruction
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub index: u64,
+    pub pc: usize,
+    pub relative_base: i64,
+    pub writes: Vec<Write>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+    pub terminated: bool,
+}
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub index: u64,
+    pub tape: Tape,
+    pub pc: usize,
+    pub terminated: bool,
+}
+
+// Undo entries are only kept since the most recent snapshot. Going back
+// further restores an older snapshot and replays forward from it using the
+// recorded inputs, so memory use is bounded by the snapshot count.
+pub struct History {
+    snapshot_interval: u64,
+    max_snapshots: usize,
+    pub snapshots: VecDeque<Snapshot>,
+    pub steps: Vec<Step>,
+    pub inputs: BTreeMap<u64, i64>,
+}
+
+impl History {
+    pub fn new(snapshot_interval: u64, max_snapshots: usize) -> Self {
+        Self {
+            snapshot_interval: snapshot_interval.max(1),
+            max_snapshots: max_snapshots.max(1),
+            snapshots: VecDeque::new(),
+            steps: Vec::new(),
+            inputs: BTreeMap::new(),
+        }
+    }
+
+    pub fn needs_snapshot(&self, index: u64) -> bool {
+        match self.snapshots.back() {
+            Some(snapshot) => index - snapshot.index >= self.snapshot_interval,
+            None => true,
+        }
+    }
+
+    pub fn push_snapshot(&mut self, snapshot: Snapshot) {
+        self.snapshots.push_back(snapshot);
+        self.steps.clear();
+
+        while self.snapshots.len() > self.max_snapshots {
+            self.snapshots.pop_front();
+        }
+
+        if let Some(oldest) = self.snapshots.front() {
+            self.inputs = self.inputs.split_off(&oldest.index);
+        }
+    }
+
+    pub fn record(&mut self, step: Step) {
+        if let Some(input) = step.input {
+            self.inputs.insert(step.index, input);
+        }
+        self.steps.push(step);
+    }
+}
@@ -1,11 +1,14 @@
 use std::collections::{BTreeMap, VecDeque};
-use std::num::ParseIntError;
 use std::convert::{TryFrom, TryInto};
 use std::str::FromStr;
 
 use anyhow::{format_err, Context, Error, Result};
+use intcode_tape::parse_values;
 use log::{debug, info, trace};
 
+use super::history::{History, Snapshot, Step, Write};
+use super::recording::{Event, Recording};
+
 #[derive(Debug)]
 enum OpCode {
     Add,
@@ -64,15 +67,17 @@ impl TryFrom<&str> for OpCode {
 
 #[derive(Clone)]
 pub struct Tape {
-    memory: BTreeMap<usize, i64>,
+    pub(crate) memory: BTreeMap<usize, i64>,
     relative_base: i64,
+    journal: Option<Vec<Write>>,
 }
 
 impl Tape {
-    fn new(program: &Vec<i64>) -> Self {
+    pub(crate) fn new(program: &[i64]) -> Self {
         let mut tape = Tape {
             memory: BTreeMap::new(),
             relative_base: 0,
+            journal: None,
         };
         for (i, item) in program.iter().enumerate() {
             tape.memory.insert(i, *item);
@@ -88,31 +93,53 @@ impl Tape {
     fn set(&mut self, offset: usize, value: i64) -> Result<()> {
         trace!("[SET] [{}] = {}", offset, value);
 
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(Write {
+                address: offset,
+                old: self.memory.get(&offset).cloned(),
+                new: value,
+            });
+        }
+
         self.memory.insert(offset, value);
 
         Ok(())
     }
 
+    fn start_journal(&mut self) {
+        self.journal = Some(Vec::new());
+    }
+
+    fn take_journal(&mut self) -> Vec<Write> {
+        self.journal.take().unwrap_or_default()
+    }
+
+    fn undo(&mut self, write: &Write) {
+        match write.old {
+            Some(value) => self.memory.insert(write.address, value),
+            None => self.memory.remove(&write.address),
+        };
+    }
+
+    // Every cell that has been set, in address order. Anything missing is zero.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, i64)> + '_ {
+        self.memory.iter().map(|(address, value)| (*address, *value))
+    }
+
     fn get_relative_base(&self) -> i64 {
         self.relative_base
     }
 
-    fn set_relative_base(&mut self, new_base: i64) {
+    pub(crate) fn set_relative_base(&mut self, new_base: i64) {
         self.relative_base = new_base;
     }
 }
 
 impl FromStr for Tape {
-    type Err = ParseIntError;
+    type Err = Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut ret = Vec::new();
-
-        for num in input.trim().split(",") {
-            ret.push(num.parse()?);
-        }
-
-        Ok(Tape::new(&ret))
+        Ok(Tape::new(&parse_values(input)?))
     }
 }
 
@@ -136,6 +163,10 @@ impl TryFrom<char> for FetchMode {
     }
 }
 
+fn to_address(value: i64) -> Result<usize> {
+    usize::try_from(value).map_err(|_| format_err!("Negative address {}", value))
+}
+
 #[derive(Debug)]
 struct Argument {
     mode: FetchMode,
@@ -146,28 +177,34 @@ impl Argument {
     fn get(&self, tape: &Tape, relative_base: i64) -> Option<i64> {
         match self.mode {
             FetchMode::Immediate => Some(self.value),
-            FetchMode::Position => tape.get(self.value as usize),
-            FetchMode::Relative => tape.get((self.value + relative_base) as usize),
+            FetchMode::Position => to_address(self.value)
+                .ok()
+                .and_then(|address| tape.get(address)),
+            FetchMode::Relative => self
+                .value
+                .checked_add(relative_base)
+                .and_then(|address| to_address(address).ok())
+                .and_then(|address| tape.get(address)),
         }
     }
 
-    fn get_for_set(&self, relative_base: i64) -> i64 {
+    fn get_for_set(&self, relative_base: i64) -> Option<i64> {
         match self.mode {
-            FetchMode::Relative => self.value + relative_base,
-            _ => self.value,
+            FetchMode::Relative => self.value.checked_add(relative_base),
+            _ => Some(self.value),
         }
     }
 }
 
 #[derive(Debug)]
-struct Instruction {
+pub(crate) struct Instruction {
     position: usize,
     opcode: OpCode,
     arguments: Vec<Argument>,
 }
 
 impl Instruction {
-    fn new(tape: &Tape, offset: usize) -> Result<Self> {
+    pub(crate) fn new(tape: &Tape, offset: usize) -> Result<Self> {
         let code = format!(
             "{:0>2}",
             tape.get(offset)
@@ -193,7 +230,11 @@ impl Instruction {
                 mode: c
                     .try_into()
                     .with_context(|| format!("Failed to parse mode \"{}\"", c))?,
-                value: tape.get(offset + i + 1).unwrap(),
+                value: tape.get(offset + i + 1).ok_or(format_err!(
+                    "Missing argument {} for instruction at offset {}",
+                    i + 1,
+                    offset
+                ))?,
             })
         }
 
@@ -242,13 +283,19 @@ impl Instruction {
             ))
     }
 
-    fn get_argument_value_for_set(&self, tape: &Tape, index: usize) -> Result<i64> {
-        Ok(self
+    fn get_argument_value_for_set(&self, tape: &Tape, index: usize) -> Result<usize> {
+        let address = self
             .get_argument(index)?
-            .get_for_set(tape.get_relative_base()))
+            .get_for_set(tape.get_relative_base())
+            .ok_or(format_err!(
+                "Argument {} for opcode {:?} overflowed",
+                index + 1,
+                self.opcode
+            ))?;
+        to_address(address)
     }
 
-    fn run(
+    pub(crate) fn run(
         &self,
         tape: &mut Tape,
         inputs: &mut VecDeque<i64>,
@@ -261,7 +308,9 @@ impl Instruction {
                 let arg1 = self.get_argument_value(tape, 0)?;
                 let arg2 = self.get_argument_value(tape, 1)?;
                 let result_offset = self.get_argument_value_for_set(tape, 2)?;
-                let result = arg1 + arg2;
+                let result = arg1
+                    .checked_add(arg2)
+                    .ok_or(format_err!("Overflow adding {} and {}", arg1, arg2))?;
 
                 trace!(
                     "[ADD] {} + {} = {}, [{}]",
@@ -271,7 +320,7 @@ impl Instruction {
                     result_offset
                 );
 
-                tape.set(result_offset as usize, result).with_context(|| {
+                tape.set(result_offset, result).with_context(|| {
                     format!(
                         "Failed to set multiplied value {} to tape index {}",
                         result, result_offset
@@ -288,7 +337,9 @@ impl Instruction {
                 let arg2 = self.get_argument_value(tape, 1)?;
                 let result_offset = self.get_argument_value_for_set(tape, 2)?;
 
-                let result = arg1 * arg2;
+                let result = arg1
+                    .checked_mul(arg2)
+                    .ok_or(format_err!("Overflow multiplying {} and {}", arg1, arg2))?;
 
                 trace!(
                     "[MUL] {} * {} = {}, [{}]",
@@ -298,7 +349,7 @@ impl Instruction {
                     result_offset
                 );
 
-                tape.set(result_offset as usize, result).with_context(|| {
+                tape.set(result_offset, result).with_context(|| {
                     format!(
                         "Failed to set multiplied value {} to tape index {}",
                         result, result_offset
@@ -319,7 +370,7 @@ impl Instruction {
                 trace!("[INP] {} -> [{}]", value, result_offset);
                 info!("[INP] {} -> [{}]", value, result_offset);
 
-                tape.set(result_offset as usize, value).with_context(|| {
+                tape.set(result_offset, value).with_context(|| {
                     format!(
                         "Failed to set input value {} to tape index {}",
                         value, result_offset
@@ -347,7 +398,7 @@ impl Instruction {
                     next_offset: if arg1 == 0 {
                         default_next_offset
                     } else {
-                        arg2 as usize
+                        to_address(arg2)?
                     },
                     relative_base: tape.get_relative_base(),
                 })
@@ -358,7 +409,7 @@ impl Instruction {
 
                 Ok(InstructionResult::Continue {
                     next_offset: if arg1 == 0 {
-                        arg2 as usize
+                        to_address(arg2)?
                     } else {
                         default_next_offset
                     },
@@ -372,7 +423,7 @@ impl Instruction {
 
                 let value = if arg1 < arg2 { 1 } else { 0 };
 
-                tape.set(result_offset as usize, value).with_context(|| {
+                tape.set(result_offset, value).with_context(|| {
                     format!(
                         "Failed to set less than value {} to tape index {}",
                         value, result_offset
@@ -391,7 +442,7 @@ impl Instruction {
 
                 let value = if arg1 == arg2 { 1 } else { 0 };
 
-                tape.set(result_offset as usize, value).with_context(|| {
+                tape.set(result_offset, value).with_context(|| {
                     format!(
                         "Failed to set less than value {} to tape index {}",
                         value, result_offset
@@ -405,10 +456,17 @@ impl Instruction {
             }
             OpCode::AdjustRelativeBase => {
                 let arg = self.get_argument_value(tape, 0)?;
-                let relative_base = tape.get_relative_base() + arg;
+                let relative_base = tape
+                    .get_relative_base()
+                    .checked_add(arg)
+                    .ok_or(format_err!(
+                        "Overflow adjusting relative base {} by {}",
+                        tape.get_relative_base(),
+                        arg
+                    ))?;
                 Ok(InstructionResult::Continue {
                     next_offset: default_next_offset,
-                    relative_base: relative_base,
+                    relative_base,
                 })
             }
             OpCode::Terminate => Ok(InstructionResult::Terminate),
@@ -416,7 +474,7 @@ impl Instruction {
     }
 }
 
-enum InstructionResult {
+pub(crate) enum InstructionResult {
     Continue {
         next_offset: usize,
         relative_base: i64,
@@ -433,6 +491,9 @@ pub struct Program {
     tape: Tape,
     pc: usize,
     state: ProgramState,
+    instruction_count: u64,
+    history: Option<History>,
+    recording: Option<Recording>,
 }
 
 impl Program {
@@ -441,44 +502,265 @@ impl Program {
             tape: tape.clone(),
             pc: 0,
             state: ProgramState::Running,
+            instruction_count: 0,
+            history: None,
+            recording: None,
+        }
+    }
+
+    fn from_snapshot(snapshot: &Snapshot) -> Self {
+        Program {
+            tape: snapshot.tape.clone(),
+            pc: snapshot.pc,
+            state: if snapshot.terminated {
+                ProgramState::Terminated
+            } else {
+                ProgramState::Running
+            },
+            instruction_count: snapshot.index,
+            history: None,
+            recording: None,
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            index: self.instruction_count,
+            tape: self.tape.clone(),
+            pc: self.pc,
+            terminated: matches!(self.state, ProgramState::Terminated),
         }
     }
 
-    pub fn run_to_next_output(&mut self, inputs: &mut VecDeque<i64>) -> Result<Option<i64>> {
+    // Records an undo log from here on, taking a full snapshot every
+    // `snapshot_interval` instructions and keeping at most `max_snapshots`
+    pub fn enable_history(&mut self, snapshot_interval: u64, max_snapshots: usize) {
+        let mut history = History::new(snapshot_interval, max_snapshots);
+        history.push_snapshot(self.snapshot());
+        self.history = Some(history);
+    }
+
+    fn execute(
+        &mut self,
+        instruction: &Instruction,
+        inputs: &mut VecDeque<i64>,
+        outputs: &mut VecDeque<i64>,
+    ) -> Result<InstructionResult> {
+        let needs_snapshot = match self.history.as_ref() {
+            Some(history) => history.needs_snapshot(self.instruction_count),
+            None => false,
+        };
+        if needs_snapshot {
+            let snapshot = self.snapshot();
+            self.history.as_mut().unwrap().push_snapshot(snapshot);
+        }
+
+        let pc = self.pc;
+        let relative_base = self.tape.get_relative_base();
+        let input = inputs.front().cloned();
+        let inputs_len = inputs.len();
+        let outputs_len = outputs.len();
+
+        if self.history.is_some() {
+            self.tape.start_journal();
+        }
+
+        let result = instruction.run(&mut self.tape, inputs, outputs);
+        let writes = self.tape.take_journal();
+        let result = result.with_context(|| format!("Failed to run instruction at offset {}", pc))?;
+
+        let terminated = match result {
+            InstructionResult::Continue {
+                next_offset,
+                relative_base,
+            } => {
+                self.pc = next_offset;
+                self.tape.set_relative_base(relative_base);
+                false
+            }
+            InstructionResult::Terminate => {
+                self.state = ProgramState::Terminated;
+                true
+            }
+        };
+
+        let input = if inputs.len() < inputs_len { input } else { None };
+        let output = if outputs.len() > outputs_len {
+            outputs.back().cloned()
+        } else {
+            None
+        };
+
+        if let Some(recording) = self.recording.as_mut() {
+            if let Some(value) = input {
+                recording.push(Event::Input {
+                    instruction: self.instruction_count,
+                    value,
+                });
+            }
+            if let Some(value) = output {
+                recording.push(Event::Output {
+                    instruction: self.instruction_count,
+                    value,
+                });
+            }
+        }
+
+        if let Some(history) = self.history.as_mut() {
+            history.record(Step {
+                index: self.instruction_count,
+                pc,
+                relative_base,
+                writes,
+                input,
+                output,
+                terminated,
+            });
+        }
+
+        self.instruction_count += 1;
+
+        Ok(result)
+    }
+
+    // Runs forward until `until` instructions have executed, feeding inputs
+    // recorded by the history
+    fn replay(&mut self, until: u64, inputs: &BTreeMap<u64, i64>) -> Result<()> {
         let mut outputs = VecDeque::new();
 
-        let mut instruction_count = 0;
-        loop {
-            let starting_len = outputs.len();
+        while self.instruction_count < until {
+            let mut step_inputs = VecDeque::new();
+            if let Some(input) = inputs.get(&self.instruction_count) {
+                step_inputs.push_back(*input);
+            }
+
             let instruction = Instruction::new(&self.tape, self.pc)
                 .with_context(|| format!("Failed to build instruction at offset {}", self.pc))?;
+            self.execute(&instruction, &mut step_inputs, &mut outputs)
+                .with_context(|| format!("Failed to replay instruction {}", self.instruction_count))?;
+        }
 
-            match instruction
-                .run(&mut self.tape, inputs, &mut outputs)
-                .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?
-            {
-                InstructionResult::Continue {
-                    next_offset,
-                    relative_base,
-                } => {
-                    self.pc = next_offset;
-                    self.tape.set_relative_base(relative_base);
-                    if outputs.len() > starting_len {
-                        break;
-                    }
-                }
-                InstructionResult::Terminate => {
-                    self.state = ProgramState::Terminated;
-                    break;
+        Ok(())
+    }
+
+    // Undoes the most recent instruction. The consumed input (if any) is in
+    // the returned step so the caller can queue it again.
+    pub fn step_back(&mut self) -> Result<Step> {
+        let history = self
+            .history
+            .as_mut()
+            .ok_or(format_err!("History is not enabled"))?;
+
+        if history.steps.is_empty() {
+            if history.snapshots.len() < 2 {
+                return Err(format_err!(
+                    "No history recorded before instruction {}",
+                    self.instruction_count
+                ));
+            }
+
+            history.snapshots.pop_back();
+            let snapshot = history.snapshots.back().unwrap().clone();
+            let inputs = history.inputs.clone();
+            let until = self.instruction_count;
+
+            // The recording already holds the replayed events, so it's set
+            // aside rather than recorded into again
+            let history = self.history.take();
+            let recording = self.recording.take();
+            *self = Program::from_snapshot(&snapshot);
+            self.history = history;
+            self.replay(until, &inputs)?;
+            self.recording = recording;
+        }
+
+        let history = self.history.as_mut().unwrap();
+        let step = history.steps.pop().unwrap();
+        history.inputs.remove(&step.index);
+
+        if let Some(recording) = self.recording.as_mut() {
+            recording.events.retain(|event| match event {
+                Event::Input { instruction, .. } | Event::Output { instruction, .. } => {
+                    *instruction < step.index
                 }
+            });
+        }
+
+        for write in step.writes.iter().rev() {
+            self.tape.undo(write);
+        }
+        self.pc = step.pc;
+        self.tape.set_relative_base(step.relative_base);
+        self.state = ProgramState::Running;
+        self.instruction_count = step.index;
+
+        Ok(step)
+    }
+
+    // Steps back until the program counter is at `pc`, returning the undone
+    // steps, most recent first
+    pub fn run_back_to(&mut self, pc: usize) -> Result<Vec<Step>> {
+        let mut steps = Vec::new();
+
+        loop {
+            let step = self
+                .step_back()
+                .with_context(|| format!("Never reached offset {} in recorded history", pc))?;
+            steps.push(step);
+
+            if self.pc == pc {
+                break;
             }
+        }
 
-            instruction_count += 1;
+        Ok(steps)
+    }
+
+    // Finds the most recent instruction that wrote to `address`, searching
+    // older snapshots by replaying them
+    pub fn last_write(&self, address: usize) -> Result<Option<Step>> {
+        let history = self
+            .history
+            .as_ref()
+            .ok_or(format_err!("History is not enabled"))?;
+
+        let find = |steps: &[Step]| {
+            steps
+                .iter()
+                .rev()
+                .find(|s| s.writes.iter().any(|w| w.address == address))
+                .cloned()
+        };
+
+        if let Some(step) = find(&history.steps) {
+            return Ok(Some(step));
         }
 
-        debug!("Ran {} instruction(s)", instruction_count);
+        let snapshots: Vec<&Snapshot> = history.snapshots.iter().collect();
+        for pair in snapshots.windows(2).rev() {
+            let mut program = Program::from_snapshot(pair[0]);
+            program.enable_history(u64::MAX, 1);
+            program.replay(pair[1].index, &history.inputs)?;
 
-        Ok(outputs.back().cloned())
+            if let Some(step) = find(&program.history.unwrap().steps) {
+                return Ok(Some(step));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Captures every input consumed and output produced from here on
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new());
+    }
+
+    pub fn get_recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    pub fn get_pc(&self) -> usize {
+        self.pc
     }
 
     pub fn run_to_next_input(&mut self, inputs: &mut VecDeque<i64>) -> Result<VecDeque<i64>> {
@@ -490,26 +772,15 @@ impl Program {
                 .with_context(|| format!("Failed to build instruction at offset {}", self.pc))?;
 
             if let OpCode::Input = instruction.opcode {
-                if inputs.len() == 0 {
+                if inputs.is_empty() {
                     break;
                 }
             }
 
-            match instruction
-                .run(&mut self.tape, inputs, &mut outputs)
-                .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?
+            if let InstructionResult::Terminate =
+                self.execute(&instruction, inputs, &mut outputs)?
             {
-                InstructionResult::Continue {
-                    next_offset,
-                    relative_base,
-                } => {
-                    self.pc = next_offset;
-                    self.tape.set_relative_base(relative_base);
-                }
-                InstructionResult::Terminate => {
-                    self.state = ProgramState::Terminated;
-                    break;
-                }
+                break;
             }
 
             instruction_count += 1;
@@ -520,6 +791,8 @@ impl Program {
         Ok(outputs.clone())
     }
 
+    // Runs to termination. Only the tests need a program run in one go.
+    #[cfg(test)]
     pub fn run(&mut self, inputs: &mut VecDeque<i64>) -> Result<VecDeque<i64>> {
         // TODO(jsvana): make this not duplicated
         let mut outputs = VecDeque::new();
@@ -528,21 +801,10 @@ impl Program {
             let instruction = Instruction::new(&self.tape, self.pc)
                 .with_context(|| format!("Failed to build instruction at offset {}", self.pc))?;
 
-            match instruction
-                .run(&mut self.tape, inputs, &mut outputs)
-                .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?
+            if let InstructionResult::Terminate =
+                self.execute(&instruction, inputs, &mut outputs)?
             {
-                InstructionResult::Continue {
-                    next_offset,
-                    relative_base,
-                } => {
-                    self.pc = next_offset;
-                    self.tape.set_relative_base(relative_base);
-                }
-                InstructionResult::Terminate => {
-                    self.state = ProgramState::Terminated;
-                    break;
-                }
+                break;
             }
         }
 
@@ -553,6 +815,10 @@ impl Program {
         &self.state
     }
 
+    pub fn get_tape(&self) -> &Tape {
+        &self.tape
+    }
+
     pub fn set_memory_value(&mut self, location: usize, value: i64) -> Result<()> {
         self.tape.set(location, value)?;
 
@@ -561,15 +827,107 @@ impl Program {
 }
 
 impl FromStr for Program {
-    type Err = ParseIntError;
+    type Err = Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut ret = Vec::new();
+        Ok(Program::new(&input.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type State = (usize, i64, BTreeMap<usize, i64>);
+
+    fn run_steps(program: &mut Program) -> Result<Vec<State>> {
+        let mut states = vec![(program.pc, program.tape.relative_base, program.tape.memory.clone())];
+        let mut inputs = VecDeque::new();
+        let mut outputs = VecDeque::new();
+
+        loop {
+            let instruction = Instruction::new(&program.tape, program.pc)?;
+            if let InstructionResult::Terminate =
+                program.execute(&instruction, &mut inputs, &mut outputs)?
+            {
+                break;
+            }
+            states.push((program.pc, program.tape.relative_base, program.tape.memory.clone()));
+        }
+
+        Ok(states)
+    }
+
+    #[test]
+    fn test_step_back_across_snapshots() -> Result<()> {
+        let mut program =
+            Program::from_str("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99")?;
+        program.enable_history(7, 1000);
 
-        for num in input.trim().split(",") {
-            ret.push(num.parse()?);
+        let mut states = run_steps(&mut program)?;
+
+        // Undo the terminate, then every instruction back to the start
+        program.step_back()?;
+        while let Some((pc, relative_base, memory)) = states.pop() {
+            assert_eq!(program.pc, pc);
+            assert_eq!(program.tape.relative_base, relative_base);
+            assert_eq!(program.tape.memory, memory);
+
+            if !states.is_empty() {
+                program.step_back()?;
+            }
         }
 
-        Ok(Program::new(&Tape::new(&ret)))
+        assert!(program.step_back().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_back_keeps_recording() -> Result<()> {
+        let mut program =
+            Program::from_str("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99")?;
+        program.enable_history(7, 1000);
+        program.start_recording();
+        run_steps(&mut program)?;
+
+        let events = program.get_recording().unwrap().events.clone();
+
+        // Far enough back to restore an earlier snapshot
+        for _ in 0..10 {
+            program.step_back()?;
+        }
+
+        let kept: Vec<Event> = events
+            .into_iter()
+            .filter(|event| match event {
+                Event::Input { instruction, .. } | Event::Output { instruction, .. } => {
+                    *instruction < program.instruction_count
+                }
+            })
+            .collect();
+        assert!(!kept.is_empty());
+        assert_eq!(program.get_recording().unwrap().events, kept);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_write_and_run_back_to() -> Result<()> {
+        let mut program =
+            Program::from_str("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99")?;
+        program.enable_history(5, 1000);
+        run_steps(&mut program)?;
+
+        // The final write to the counter is the last ADD
+        let step = program.last_write(100)?.unwrap();
+        assert_eq!(step.pc, 4);
+        assert_eq!(step.writes[0].new, 16);
+
+        let steps = program.run_back_to(4)?;
+        assert_eq!(steps.len(), 4);
+        assert_eq!(program.tape.get(100), Some(15));
+
+        Ok(())
     }
 }
@@ -1,18 +1,33 @@
+mod arcade;
+mod history;
 mod intcode;
+mod memory;
+#[path = "../../common/recording.rs"]
+mod recording;
+mod screen;
+mod strategy;
 
-use std::cmp::{max, min};
 use std::collections::{BTreeMap, VecDeque};
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 use std::fmt;
+use std::io::{stdout, Write};
 use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
 use anyhow::{format_err, Error, Result};
 
-use crate::intcode::{Program, ProgramState};
+use crate::arcade::{Decoder, Event};
+use crate::history::Step;
+use crate::intcode::{Program, ProgramState, Tape};
+use crate::memory::{diff, dump, Radix, Scanner};
+use crate::recording::{replay, Recording};
+use crate::screen::{Film, Frame, Screen};
+use crate::strategy::{FollowBall, JoystickStrategy, Predictive, View};
 
 type Map = BTreeMap<i64, BTreeMap<i64, Tile>>;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 enum Tile {
     Empty,
     Wall,
@@ -65,43 +80,228 @@ fn count_blocks(map: &Map) -> u64 {
     total
 }
 
-fn print_map(map: &Map) {
-    let mut min_x = std::i64::MAX;
-    let mut max_x = std::i64::MIN;
-    let mut min_y = std::i64::MAX;
-    let mut max_y = std::i64::MIN;
+// Draws a batch of cabinet output onto the map, keeping track of the score
+fn draw_outputs(
+    decoder: &mut Decoder<Tile>,
+    map: &mut Map,
+    score: &mut i64,
+    outputs: &[i64],
+) -> Result<()> {
+    let first = map.is_empty();
+    for event in decoder.feed(outputs.iter().cloned())? {
+        match event {
+            Event::DrawTile { x, y, tile } => set_value(map, x, y, tile),
+            Event::SetScore(value) => *score = value,
+        }
+    }
+
+    // The cabinet doesn't say how big its screen is, but the first frame draws
+    // all of it, so nothing drawn later may fall outside that
+    if first && !map.is_empty() {
+        let (width, height) = screen_size(map);
+        decoder.set_bounds(width, height);
+    }
+
+    Ok(())
+}
+
+// One past the furthest cell drawn in each direction
+fn screen_size(map: &Map) -> (i64, i64) {
+    let width = map
+        .values()
+        .filter_map(|row| row.keys().next_back())
+        .max()
+        .map_or(0, |x| x + 1);
+    let height = map.keys().next_back().map_or(0, |y| y + 1);
+
+    (width, height)
+}
+
+fn find_tile(map: &Map, wanted: Tile) -> Option<(i64, i64)> {
+    map.iter()
+        .flat_map(|(y, row)| row.iter().map(move |(x, tile)| (*x, *y, *tile)))
+        .find(|(_, _, tile)| *tile == wanted)
+        .map(|(x, y, _)| (x, y))
+}
+
+// Games that go on longer than this are assumed to be stuck
+const MAX_INPUTS: usize = 100_000;
+
+struct Outcome {
+    inputs: usize,
+    // Inputs that actually moved the paddle
+    moves: usize,
+    score: i64,
+    blocks: u64,
+}
+
+// Plays a whole game with `strategy` on the joystick
+fn run_game(mut program: Program, strategy: &mut dyn JoystickStrategy) -> Result<Outcome> {
+    let mut inputs = VecDeque::new();
+    let mut decoder = Decoder::new();
+    let mut map = Map::new();
+    let mut score = 0;
+    let mut history = Vec::new();
+    let mut sent = 0;
+    let mut moves = 0;
+
+    loop {
+        let outputs: Vec<i64> = program.run_to_next_input(&mut inputs)?.into_iter().collect();
+        draw_outputs(&mut decoder, &mut map, &mut score, &outputs)?;
+
+        if let ProgramState::Terminated = *program.get_state() {
+            decoder.finish()?;
+            break;
+        }
+        if sent == MAX_INPUTS {
+            break;
+        }
 
-    for (y, row) in map.iter() {
-        for x in row.keys() {
-            min_x = min(*x, min_x);
-            max_x = max(*x, max_x);
-            min_y = min(*y, min_y);
-            max_y = max(*y, max_y);
+        let ball = find_tile(&map, Tile::Ball).ok_or(format_err!("No ball on screen"))?;
+        let paddle = find_tile(&map, Tile::Paddle).ok_or(format_err!("No paddle on screen"))?;
+        let joystick = strategy.choose(&View {
+            map: &map,
+            ball,
+            paddle,
+            history: &history,
+        })?;
+        inputs.push_back(joystick);
+        history.push(ball);
+        sent += 1;
+        if joystick != 0 {
+            moves += 1;
         }
     }
 
-    for y in min_y..max_y + 1 {
-        for x in min_x..max_x + 1 {
-            let tile = *map
-                .get(&y)
-                .unwrap_or(&BTreeMap::new())
-                .get(&x)
-                .unwrap_or(&Tile::Empty);
-            print!("{}", tile);
+    Ok(Outcome {
+        inputs: sent,
+        moves,
+        score,
+        blocks: count_blocks(&map),
+    })
+}
+
+// Runs every automatic strategy on a fresh cabinet and reports how many
+// times each moves the paddle to clear the screen. The cabinet reads one
+// input per frame, and the ball bounces off the paddle the same way wherever
+// it's hit, so every strategy that clears the screen sends the same number of
+// inputs. Moves are what tell them apart.
+fn compare(tape: &Tape) -> Result<()> {
+    let strategies: Vec<Box<dyn JoystickStrategy>> =
+        vec![Box::new(FollowBall), Box::new(Predictive)];
+
+    println!("Every cleared game lasts as many frames, so strategies are compared on moves");
+    for mut strategy in strategies {
+        let outcome = run_game(Program::new(tape), strategy.as_mut())?;
+        if outcome.blocks == 0 {
+            println!(
+                "{}: cleared in {} move(s) over {} frame(s), score {}",
+                strategy.name(),
+                outcome.moves,
+                outcome.inputs,
+                outcome.score
+            );
+        } else {
+            println!(
+                "{}: {} block(s) left after {} move(s) over {} frame(s), score {}",
+                strategy.name(),
+                outcome.blocks,
+                outcome.moves,
+                outcome.inputs,
+                outcome.score
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// Frames per second for watching a game, where 0 means no waiting at all
+fn pause(speed: f64) {
+    if speed > 0.0 {
+        thread::sleep(Duration::from_secs_f64(1.0 / speed));
+    }
+}
+
+// Shows a recorded game frame by frame
+fn play(film: &Film, speed: f64) -> Result<()> {
+    let mut screen = Screen::new();
+    let mut decoder = Decoder::new();
+    let mut map = Map::new();
+    let mut score = 0;
+
+    for frame in film.frames.iter() {
+        draw_outputs(&mut decoder, &mut map, &mut score, &frame.outputs)?;
+        print!("{}", screen.frame(&map, score, count_blocks(&map)));
+        stdout().flush()?;
+        pause(speed);
+    }
+
+    Ok(())
+}
+
+include!("../../common/args.rs");
+
+// Parses "ADDRESS=VALUE"
+fn parse_patch(patch: &str) -> Result<(usize, i64)> {
+    let parts: Vec<&str> = patch.split('=').collect();
+    if parts.len() != 2 {
+        return Err(format_err!("Patch \"{}\" should look like ADDRESS=VALUE", patch));
+    }
+
+    Ok((parts[0].trim().parse()?, parts[1].trim().parse()?))
+}
+
+fn print_step(step: &Step) {
+    let writes: Vec<String> = step
+        .writes
+        .iter()
+        .map(|w| format!("[{}] {:?} -> {}", w.address, w.old, w.new))
+        .collect();
+
+    println!(
+        "#{} pc {} rb {}: {}{}{}{}",
+        step.index,
+        step.pc,
+        step.relative_base,
+        writes.join(", "),
+        step.input.map(|i| format!(" input {}", i)).unwrap_or_default(),
+        step.output.map(|o| format!(" output {}", o)).unwrap_or_default(),
+        if step.terminated { " terminate" } else { "" },
+    );
+}
+
+// Walks the program back over its last `count` instructions
+fn rewind(program: &mut Program, count: usize) {
+    for _ in 0..count {
+        match program.step_back() {
+            Ok(step) => print_step(&step),
+            Err(e) => {
+                println!("Stopped rewinding: {}", e);
+                break;
+            }
         }
-        println!("");
     }
 }
 
 fn set_value(map: &mut Map, x: i64, y: i64, value: Tile) {
     *map.entry(y)
-        .or_insert(BTreeMap::new())
+        .or_default()
         .entry(x)
         .or_insert(Tile::Empty) = value;
 }
 
 fn main() -> Result<()> {
-    env_logger::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let speed = match flag_value("--speed") {
+        Some(speed) => speed.parse()?,
+        None => 60.0,
+    };
+
+    if let Some(filename) = flag_value("--play") {
+        return play(&Film::load(&filename)?, speed);
+    }
 
     let mut inputs = VecDeque::new();
 
@@ -111,51 +311,221 @@ fn main() -> Result<()> {
 
     program.set_memory_value(0, 2)?;
 
+    for patch in flag_values("--patch") {
+        let (address, value) = parse_patch(&patch)?;
+        program.set_memory_value(address, value)?;
+    }
+
+    if std::env::args().any(|arg| arg == "--compare") {
+        return compare(program.get_tape());
+    }
+
+    if let Some(filename) = flag_value("--replay") {
+        let recording = Recording::load(&filename)?;
+        replay(&mut program, &recording)?;
+        println!(
+            "Replay matched {} recorded event(s)",
+            recording.events.len()
+        );
+        return Ok(());
+    }
+
+    let record_to = flag_value("--record");
+    if record_to.is_some() {
+        program.start_recording();
+    }
+
+    let debug = std::env::args().any(|arg| arg == "--debug");
+    if debug {
+        program.enable_history(10_000, 16);
+    }
+
+    // Finds the cells holding the score or the block count by scanning for
+    // cells that change alongside them after every frame
+    let scan = flag_value("--scan");
+    if let Some(other) = scan.as_deref().filter(|s| *s != "score" && *s != "blocks") {
+        return Err(format_err!("Can't scan for \"{}\"", other));
+    }
+    let mut scanner: Option<Scanner> = None;
+    let mut last_score = 0;
+    let mut last_blocks = 0;
+    let start = program.get_tape().clone();
+
+    // Watching draws each frame as it comes out of the cabinet
+    let mut screen = if std::env::args().any(|arg| arg == "--watch") {
+        Some(Screen::new())
+    } else {
+        None
+    };
+    let film_to = flag_value("--record-game");
+    let mut film = Film::new();
+
+    let mut decoder = Decoder::new();
     let mut map = BTreeMap::new();
     let mut score = 0;
 
-    let mut paddle_x = 0;
+    let mut strategy =
+        strategy::by_name(&flag_value("--strategy").unwrap_or_else(|| "follow".to_string()))?;
+
+    let mut paddle = (0, 0);
 
-    let mut ball_x = 0;
+    let mut ball = (0, 0);
+
+    let mut history = Vec::new();
+
+    let mut last_input_pc = 0;
 
     loop {
         if let ProgramState::Terminated = *program.get_state() {
             break;
         }
 
-        let mut outputs = program.run_to_next_input(&mut inputs)?;
+        let outputs = match program.run_to_next_input(&mut inputs) {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                if debug {
+                    rewind(&mut program, 20);
+                }
+                return Err(e);
+            }
+        };
 
-        while outputs.len() > 0 {
-            let x = outputs.pop_front().unwrap();
-            let y = outputs.pop_front().unwrap();
+        if let ProgramState::Running = *program.get_state() {
+            last_input_pc = program.get_pc();
+        }
 
-            if x == -1 && y == 0 {
-                score = outputs.pop_front().unwrap();
-            } else {
-                let tile: Tile = outputs.pop_front().unwrap().try_into()?;
+        let outputs: Vec<i64> = outputs.into_iter().collect();
+        draw_outputs(&mut decoder, &mut map, &mut score, &outputs)?;
+        paddle = find_tile(&map, Tile::Paddle).unwrap_or(paddle);
+        ball = find_tile(&map, Tile::Ball).unwrap_or(ball);
 
-                if let Tile::Paddle = tile {
-                    paddle_x = x;
-                }
+        if let Some(screen) = screen.as_mut() {
+            print!("{}", screen.frame(&map, score, count_blocks(&map)));
+            stdout().flush()?;
+            pause(speed);
+        }
 
-                if let Tile::Ball = tile {
-                    ball_x = x;
+        if scan.is_some() {
+            let blocks = count_blocks(&map) as i64;
+            match scanner.as_mut() {
+                // The first frame draws the whole screen, so scanning starts after it
+                None => scanner = Some(Scanner::new(program.get_tape())),
+                Some(scanner) => {
+                    let delta = match scan.as_deref() {
+                        Some("score") => score - last_score,
+                        _ => blocks - last_blocks,
+                    };
+                    scanner.changed_by(program.get_tape(), delta);
                 }
-
-                set_value(&mut map, x, y, tile);
             }
+            last_score = score;
+            last_blocks = blocks;
         }
 
-        if paddle_x < ball_x {
-            inputs.push_back(1);
-        } else if paddle_x > ball_x {
-            inputs.push_back(-1);
-        } else {
-            inputs.push_back(0);
-        }
+        let joystick = match *program.get_state() {
+            ProgramState::Terminated => None,
+            _ => Some(strategy.choose(&View {
+                map: &map,
+                ball,
+                paddle,
+                history: &history,
+            })?),
+        };
+        history.push(ball);
+        inputs.extend(joystick);
+
+        film.frames.push(Frame { outputs, joystick });
     }
 
+    decoder.finish()?;
+
     println!("Score: {}", score);
 
+    if let Some(scanner) = scanner {
+        let candidates = scanner.candidates();
+        println!("{} candidate(s) for {}:", candidates.len(), scan.unwrap());
+        for (address, value) in candidates {
+            println!("  [{}] = {}", address, value);
+        }
+    }
+
+    if let Some(radix) = flag_value("--dump") {
+        let radix = match radix.as_str() {
+            "dec" => Radix::Decimal,
+            "hex" => Radix::Hex,
+            _ => return Err(format_err!("Unknown radix \"{}\", expected dec or hex", radix)),
+        };
+        println!("{}", dump(program.get_tape(), radix));
+    }
+
+    if std::env::args().any(|arg| arg == "--diff") {
+        for change in diff(&start, program.get_tape()) {
+            println!("[{}] {} -> {}", change.address, change.old, change.new);
+        }
+    }
+
+    if let Some(filename) = film_to {
+        film.save(&filename)?;
+    }
+
+    if let Some(filename) = record_to {
+        program.get_recording().unwrap().save(&filename)?;
+    }
+
+    let blocks = count_blocks(&map);
+    if blocks > 0 {
+        println!("Game ended with {} block(s) left", blocks);
+        if debug {
+            // Everything the cabinet did after the last joystick input
+            for step in program.run_back_to(last_input_pc)?.iter().rev() {
+                print_step(step);
+            }
+        }
+    }
+
+    if debug {
+        for address in flag_values("--last-write") {
+            let address = address.parse()?;
+            match program.last_write(address)? {
+                Some(step) => print_step(&step),
+                None => println!("Nothing wrote to {} in recorded history", address),
+            }
+        }
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_triple_split_across_first_frame() -> Result<()> {
+        let mut decoder = Decoder::new();
+        let mut map = Map::new();
+        let mut score = 0;
+
+        // The first frame is 2x2 and ends partway through the paddle's triple
+        draw_outputs(
+            &mut decoder,
+            &mut map,
+            &mut score,
+            &[0, 0, 1, 1, 1, 2, 0, 1],
+        )?;
+        draw_outputs(&mut decoder, &mut map, &mut score, &[3, -1, 0, 7])?;
+
+        assert_eq!(find_tile(&map, Tile::Paddle), Some((0, 1)));
+        assert_eq!(score, 7);
+
+        // The first frame also set the screen size
+        assert_eq!(
+            draw_outputs(&mut decoder, &mut map, &mut score, &[2, 0, 0])
+                .unwrap_err()
+                .to_string(),
+            "Tile 0 drawn off screen at (2, 0)"
+        );
+
+        Ok(())
+    }
+}
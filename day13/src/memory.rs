@@ -0,0 +1,218 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::intcode::Tape;
+
+// Cells per row of a dump
+const COLUMNS: usize = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Radix {
+    Decimal,
+    Hex,
+}
+
+impl Radix {
+    fn format(&self, value: i64) -> String {
+        match self {
+            Radix::Decimal => value.to_string(),
+            Radix::Hex if value < 0 => format!("-{:x}", value.unsigned_abs()),
+            Radix::Hex => format!("{:x}", value),
+        }
+    }
+}
+
+// Lays memory out in rows of COLUMNS cells, each labelled with the address of
+// its first cell. Rows where nothing has ever been set are left out.
+pub fn dump(tape: &Tape, radix: Radix) -> String {
+    let cells: BTreeMap<usize, i64> = tape.cells().collect();
+    let rows: BTreeSet<usize> = cells
+        .keys()
+        .map(|address| address - address % COLUMNS)
+        .collect();
+
+    let width = cells
+        .values()
+        .map(|value| radix.format(*value).len())
+        .max()
+        .unwrap_or(1);
+    let address_width = rows
+        .iter()
+        .next_back()
+        .map_or(1, |row| radix.format(*row as i64).len());
+
+    let mut lines = Vec::new();
+    for row in rows {
+        let values: Vec<String> = (row..row + COLUMNS)
+            .map(|address| {
+                let value = radix.format(*cells.get(&address).unwrap_or(&0));
+                format!("{:>width$}", value, width = width)
+            })
+            .collect();
+        lines.push(format!(
+            "{:>width$}: {}",
+            radix.format(row as i64),
+            values.join(" "),
+            width = address_width
+        ));
+    }
+
+    lines.join("\n")
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub address: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+// Cells missing from either snapshot read as zero, as they do in the VM
+pub fn diff(before: &Tape, after: &Tape) -> Vec<Change> {
+    let before: BTreeMap<usize, i64> = before.cells().collect();
+    let after: BTreeMap<usize, i64> = after.cells().collect();
+    let addresses: BTreeSet<usize> = before.keys().chain(after.keys()).cloned().collect();
+
+    addresses
+        .into_iter()
+        .filter_map(|address| {
+            let old = *before.get(&address).unwrap_or(&0);
+            let new = *after.get(&address).unwrap_or(&0);
+            if old == new {
+                None
+            } else {
+                Some(Change { address, old, new })
+            }
+        })
+        .collect()
+}
+
+// Narrows down where a variable lives by watching how it changes, the way
+// game cheat engines do. Every cell starts out as a candidate, and each scan
+// keeps only the ones that moved by the amount the variable did.
+pub struct Scanner {
+    previous: BTreeMap<usize, i64>,
+    candidates: Option<BTreeSet<usize>>,
+}
+
+impl Scanner {
+    pub fn new(tape: &Tape) -> Self {
+        Self {
+            previous: tape.cells().collect(),
+            candidates: None,
+        }
+    }
+
+    // Keeps the candidates that changed by exactly `delta` since the last
+    // scan. Returns how many are left.
+    pub fn changed_by(&mut self, tape: &Tape, delta: i64) -> usize {
+        let current: BTreeMap<usize, i64> = tape.cells().collect();
+        let addresses: BTreeSet<usize> = match self.candidates.take() {
+            Some(candidates) => candidates,
+            None => self
+                .previous
+                .keys()
+                .chain(current.keys())
+                .cloned()
+                .collect(),
+        };
+
+        let candidates: BTreeSet<usize> = addresses
+            .into_iter()
+            .filter(|address| {
+                let old = *self.previous.get(address).unwrap_or(&0);
+                let new = *current.get(address).unwrap_or(&0);
+                new.checked_sub(old) == Some(delta)
+            })
+            .collect();
+        let remaining = candidates.len();
+
+        self.candidates = Some(candidates);
+        self.previous = current;
+
+        remaining
+    }
+
+    // Remaining candidates along with their value as of the last scan
+    pub fn candidates(&self) -> Vec<(usize, i64)> {
+        match self.candidates.as_ref() {
+            Some(candidates) => candidates
+                .iter()
+                .map(|address| (*address, *self.previous.get(address).unwrap_or(&0)))
+                .collect(),
+            None => self.previous.iter().map(|(a, v)| (*a, *v)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::Result;
+
+    use crate::intcode::Program;
+
+    #[test]
+    fn test_dump() -> Result<()> {
+        let mut program: Program = "1,8,0,11,0".parse()?;
+        program.set_memory_value(9, 255)?;
+
+        assert_eq!(
+            dump(program.get_tape(), Radix::Hex),
+            "0:  1  8  0  b  0  0  0  0\n8:  0 ff  0  0  0  0  0  0"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let mut program: Program = "1,5,0,7,-3".parse()?;
+        let before = program.get_tape().clone();
+
+        program.set_memory_value(1, 8)?;
+        program.set_memory_value(3, 11)?;
+        program.set_memory_value(4, 0)?;
+
+        assert_eq!(
+            diff(&before, program.get_tape()),
+            vec![
+                Change {
+                    address: 1,
+                    old: 5,
+                    new: 8
+                },
+                Change {
+                    address: 3,
+                    old: 7,
+                    new: 11
+                },
+                Change {
+                    address: 4,
+                    old: -3,
+                    new: 0
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan() -> Result<()> {
+        let mut program: Program = "1,5,0,7,-3".parse()?;
+        let mut scanner = Scanner::new(program.get_tape());
+
+        // Cells 1 and 3 both go up by two, but only 3 does it again
+        program.set_memory_value(1, 7)?;
+        program.set_memory_value(3, 9)?;
+        program.set_memory_value(4, 0)?;
+        assert_eq!(scanner.changed_by(program.get_tape(), 2), 2);
+        program.set_memory_value(1, 8)?;
+        program.set_memory_value(3, 11)?;
+        assert_eq!(scanner.changed_by(program.get_tape(), 2), 1);
+        assert_eq!(scanner.candidates(), vec![(3, 11)]);
+
+        Ok(())
+    }
+}
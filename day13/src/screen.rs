@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{format_err, Context, Error, Result};
+
+use crate::{Map, Tile};
+
+// Draws the cabinet's screen in a terminal with ANSI escape codes. After the
+// first frame only cells that changed are redrawn, so the game can be watched
+// at full speed without flicker.
+pub struct Screen {
+    drawn: BTreeMap<(i64, i64), Tile>,
+    status_row: i64,
+    cleared: bool,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self {
+            drawn: BTreeMap::new(),
+            status_row: 0,
+            cleared: false,
+        }
+    }
+
+    // Everything needed to bring the terminal up to date with `map`. Cells
+    // with negative coordinates have nowhere to go and are skipped.
+    pub fn frame(&mut self, map: &Map, score: i64, blocks: u64) -> String {
+        let mut out = String::new();
+        if !self.cleared {
+            out.push_str("\x1B[2J");
+            self.cleared = true;
+        }
+
+        for (y, row) in map.iter().filter(|(y, _)| **y >= 0) {
+            for (x, tile) in row.iter().filter(|(x, _)| **x >= 0) {
+                if self.drawn.get(&(*x, *y)) == Some(tile) {
+                    continue;
+                }
+
+                out.push_str(&format!("\x1B[{};{}H{}", y + 1, x + 1, tile));
+                self.drawn.insert((*x, *y), *tile);
+                self.status_row = self.status_row.max(y + 3);
+            }
+        }
+
+        out.push_str(&format!(
+            "\x1B[{};1H\x1B[2KScore: {}  Blocks: {}\n",
+            self.status_row.max(1),
+            score,
+            blocks
+        ));
+
+        out
+    }
+}
+
+// One batch of cabinet output, along with the joystick input sent back after
+// it. The last frame has no input since the game is over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub outputs: Vec<i64>,
+    pub joystick: Option<i64>,
+}
+
+// A whole game as frames, so it can be played back without the program
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Film {
+    pub frames: Vec<Frame>,
+}
+
+impl Film {
+    pub fn new() -> Self {
+        Self { frames: Vec::new() }
+    }
+
+    pub fn load(filename: &str) -> Result<Self> {
+        std::fs::read_to_string(filename)
+            .with_context(|| format!("Failed to read game {}", filename))?
+            .parse()
+    }
+
+    pub fn save(&self, filename: &str) -> Result<()> {
+        std::fs::write(filename, self.to_string())
+            .with_context(|| format!("Failed to write game {}", filename))
+    }
+}
+
+// One frame per line, the outputs followed by "> JOYSTICK" if there is one
+impl fmt::Display for Film {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for frame in self.frames.iter() {
+            let outputs: Vec<String> = frame.outputs.iter().map(|o| o.to_string()).collect();
+            write!(f, "{}", outputs.join(" "))?;
+            if let Some(joystick) = frame.joystick {
+                write!(f, " > {}", joystick)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Film {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut film = Film::new();
+
+        for (i, line) in input.lines().enumerate() {
+            let parts: Vec<&str> = line.split('>').collect();
+            let parse = |value: &str| -> Result<i64> {
+                value
+                    .parse()
+                    .with_context(|| format!("Bad value \"{}\" on line {}", value, i + 1))
+            };
+
+            let outputs = parts[0]
+                .split_whitespace()
+                .map(parse)
+                .collect::<Result<Vec<i64>>>()?;
+            let joystick = match parts.as_slice() {
+                [_] => None,
+                [_, joystick] => Some(parse(joystick.trim())?),
+                _ => return Err(format_err!("Too many inputs on line {}", i + 1)),
+            };
+
+            film.frames.push(Frame { outputs, joystick });
+        }
+
+        Ok(film)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(tiles: &[(i64, i64, Tile)]) -> Map {
+        let mut map = Map::new();
+        for (x, y, tile) in tiles.iter() {
+            map.entry(*y).or_default().insert(*x, *tile);
+        }
+        map
+    }
+
+    #[test]
+    fn test_film_round_trip() -> Result<()> {
+        let film: Film = "0 0 1 1 0 4 -1 0 7 > -1\n1 0 2\n".parse()?;
+        assert_eq!(film.frames[0].joystick, Some(-1));
+        assert_eq!(film.frames[1].outputs, vec![1, 0, 2]);
+        assert_eq!(film.frames[1].joystick, None);
+        assert_eq!(film.to_string(), "0 0 1 1 0 4 -1 0 7 > -1\n1 0 2\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_film_errors() {
+        let error = |input: &str| input.parse::<Film>().unwrap_err().to_string();
+
+        assert_eq!(error("1 0 2\n1 x 2\n"), "Bad value \"x\" on line 2");
+        assert_eq!(error("1 0 2 > 1 > 0\n"), "Too many inputs on line 1");
+    }
+
+    #[test]
+    fn test_first_frame_clears() {
+        let mut screen = Screen::new();
+        assert_eq!(
+            screen.frame(&map(&[(0, 0, Tile::Wall), (1, 0, Tile::Ball)]), 0, 0),
+            "\x1B[2J\x1B[1;1H+\x1B[1;2HO\x1B[3;1H\x1B[2KScore: 0  Blocks: 0\n"
+        );
+    }
+
+    #[test]
+    fn test_redraws_only_changes() {
+        let mut screen = Screen::new();
+        screen.frame(&map(&[(0, 0, Tile::Wall), (1, 0, Tile::Ball)]), 0, 0);
+
+        assert_eq!(
+            screen.frame(&map(&[(0, 0, Tile::Wall), (1, 0, Tile::Block)]), 7, 1),
+            "\x1B[1;2H#\x1B[3;1H\x1B[2KScore: 7  Blocks: 1\n"
+        );
+    }
+
+    #[test]
+    fn test_skips_negative_cells() {
+        let mut screen = Screen::new();
+        assert_eq!(
+            screen.frame(&map(&[(-1, 0, Tile::Wall), (0, -1, Tile::Wall)]), 0, 0),
+            "\x1B[2J\x1B[1;1H\x1B[2KScore: 0  Blocks: 0\n"
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use std::io::{stdin, stdout, Write};
+
+use anyhow::{format_err, Context, Result};
+
+use crate::{Map, Tile};
+
+// Far more than any sensible flight across the screen takes
+const MAX_PREDICTED_STEPS: usize = 10_000;
+
+// What a strategy gets to look at before each joystick move
+pub struct View<'a> {
+    pub map: &'a Map,
+    pub ball: (i64, i64),
+    pub paddle: (i64, i64),
+    // Where the ball was before each earlier move, oldest first
+    pub history: &'a [(i64, i64)],
+}
+
+pub trait JoystickStrategy {
+    fn name(&self) -> &str;
+
+    // -1 to move the paddle left, 1 to move it right, 0 to stay put
+    fn choose(&mut self, view: &View) -> Result<i64>;
+}
+
+fn toward(from: i64, to: i64) -> i64 {
+    (to - from).signum()
+}
+
+// Keeps the paddle under the ball. Always works, but wastes moves chasing
+// the ball back and forth.
+pub struct FollowBall;
+
+impl JoystickStrategy for FollowBall {
+    fn name(&self) -> &str {
+        "follow"
+    }
+
+    fn choose(&mut self, view: &View) -> Result<i64> {
+        Ok(toward(view.paddle.0, view.ball.0))
+    }
+}
+
+// Works out where the ball will come down from the way it's moving and heads
+// straight there, bouncing it off walls and blocks along the way
+pub struct Predictive;
+
+fn solid(map: &Map, x: i64, y: i64) -> bool {
+    matches!(
+        map.get(&y).and_then(|row| row.get(&x)),
+        Some(Tile::Wall) | Some(Tile::Block)
+    )
+}
+
+impl Predictive {
+    fn landing_x(&self, view: &View) -> Option<i64> {
+        let previous = view.history.last()?;
+        let (mut x, mut y) = view.ball;
+        let (mut dx, mut dy) = (x - previous.0, y - previous.1);
+        if dx.abs() != 1 || dy.abs() != 1 {
+            return None;
+        }
+
+        let landing_y = view.paddle.1 - 1;
+        for _ in 0..MAX_PREDICTED_STEPS {
+            if y == landing_y && dy > 0 {
+                return Some(x);
+            }
+
+            let mut bounced = false;
+            if solid(view.map, x + dx, y) {
+                dx = -dx;
+                bounced = true;
+            }
+            if solid(view.map, x, y + dy) {
+                dy = -dy;
+                bounced = true;
+            }
+            if !bounced && solid(view.map, x + dx, y + dy) {
+                dx = -dx;
+                dy = -dy;
+            }
+
+            x += dx;
+            y += dy;
+        }
+
+        None
+    }
+}
+
+impl JoystickStrategy for Predictive {
+    fn name(&self) -> &str {
+        "predict"
+    }
+
+    fn choose(&mut self, view: &View) -> Result<i64> {
+        let target = self.landing_x(view).unwrap_or(view.ball.0);
+        Ok(toward(view.paddle.0, target))
+    }
+}
+
+// Asks whoever's at the keyboard, a line at a time
+pub struct Human;
+
+impl JoystickStrategy for Human {
+    fn name(&self) -> &str {
+        "human"
+    }
+
+    fn choose(&mut self, _: &View) -> Result<i64> {
+        loop {
+            print!("Move (a = left, s = stay, d = right): ");
+            stdout().flush()?;
+
+            let mut line = String::new();
+            if stdin()
+                .read_line(&mut line)
+                .context("Failed to read move")?
+                == 0
+            {
+                return Err(format_err!("Ran out of moves"));
+            }
+
+            match line.trim() {
+                "a" => return Ok(-1),
+                "s" | "" => return Ok(0),
+                "d" => return Ok(1),
+                other => println!("Unknown move \"{}\"", other),
+            }
+        }
+    }
+}
+
+pub fn by_name(name: &str) -> Result<Box<dyn JoystickStrategy>> {
+    match name {
+        "follow" => Ok(Box::new(FollowBall)),
+        "predict" => Ok(Box::new(Predictive)),
+        "human" => Ok(Box::new(Human)),
+        _ => Err(format_err!(
+            "Unknown strategy \"{}\", expected follow, predict or human",
+            name
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A box with walls at x = 0 and x = 6. The paddle is on row 5, so the
+    // ball lands on row 4.
+    fn walls() -> Map {
+        let mut map = Map::new();
+        for y in 0..6 {
+            map.entry(y).or_default().insert(0, Tile::Wall);
+            map.entry(y).or_default().insert(6, Tile::Wall);
+        }
+        map
+    }
+
+    #[test]
+    fn test_follow_ball() -> Result<()> {
+        let map = walls();
+        let view = |paddle_x: i64| View {
+            map: &map,
+            ball: (3, 2),
+            paddle: (paddle_x, 5),
+            history: &[],
+        };
+
+        assert_eq!(FollowBall.choose(&view(1))?, 1);
+        assert_eq!(FollowBall.choose(&view(3))?, 0);
+        assert_eq!(FollowBall.choose(&view(5))?, -1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_predict_off_wall() -> Result<()> {
+        // Heading down and right from (4, 1), the ball hits the right wall
+        // and comes down at x = 3
+        let map = walls();
+        let view = View {
+            map: &map,
+            ball: (4, 1),
+            paddle: (1, 5),
+            history: &[(3, 0)],
+        };
+
+        assert_eq!(Predictive.landing_x(&view), Some(3));
+        assert_eq!(Predictive.choose(&view)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_predict_off_block() -> Result<()> {
+        // The block turns the ball back left before it reaches the wall
+        let mut map = walls();
+        map.entry(2).or_default().insert(5, Tile::Block);
+        let view = View {
+            map: &map,
+            ball: (4, 2),
+            paddle: (5, 5),
+            history: &[(3, 1)],
+        };
+
+        assert_eq!(Predictive.landing_x(&view), Some(2));
+        assert_eq!(Predictive.choose(&view)?, -1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_predict_without_history() -> Result<()> {
+        // With nothing to go on it follows the ball instead
+        let map = walls();
+        let view = View {
+            map: &map,
+            ball: (4, 1),
+            paddle: (5, 5),
+            history: &[],
+        };
+
+        assert_eq!(Predictive.landing_x(&view), None);
+        assert_eq!(Predictive.choose(&view)?, -1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_by_name() -> Result<()> {
+        assert_eq!(by_name("follow")?.name(), "follow");
+        assert_eq!(by_name("predict")?.name(), "predict");
+        assert_eq!(
+            by_name("random").err().map(|e| e.to_string()),
+            Some("Unknown strategy \"random\", expected follow, predict or human".to_string())
+        );
+
+        Ok(())
+    }
+}
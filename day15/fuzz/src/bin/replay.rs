@@ -0,0 +1,219 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use anyhow::{format_err, Context, Result};
+
+// Values that tend to shake out edge cases when dropped into a program
+const INTERESTING: &[i64] = &[
+    0,
+    1,
+    -1,
+    2,
+    99,
+    109,
+    203,
+    204,
+    1105,
+    1106,
+    1201,
+    2101,
+    21101,
+    1 << 32,
+    i64::MAX,
+    i64::MIN,
+];
+
+// Small xorshift generator so mutation runs are reproducible from a seed
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+fn mutate(input: &[u8], rng: &mut Rng) -> Vec<u8> {
+    let text = String::from_utf8_lossy(input);
+    let mut values: Vec<String> = text.trim().split(',').map(|v| v.to_string()).collect();
+
+    for _ in 0..=rng.below(4) {
+        let index = rng.below(values.len());
+        match rng.below(5) {
+            0 => values[index] = INTERESTING[rng.below(INTERESTING.len())].to_string(),
+            1 => values[index] = (rng.next() as i64 >> rng.below(64)).to_string(),
+            2 => {
+                let other = values[rng.below(values.len())].clone();
+                values[index] = other;
+            }
+            3 => {
+                values.truncate(index.max(1));
+            }
+            _ => {
+                let value = INTERESTING[rng.below(INTERESTING.len())].to_string();
+                values.insert(index, value);
+            }
+        }
+    }
+
+    values.join(",").into_bytes()
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            collect_files(&entry?.path(), files)?;
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+
+    Ok(())
+}
+
+// Returns the panic message if `target` panicked on `data`
+fn check(target: fn(&[u8]), data: &[u8]) -> Option<String> {
+    panic::catch_unwind(AssertUnwindSafe(|| target(data)))
+        .err()
+        .map(|payload| {
+            payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| "unknown panic".to_string())
+        })
+}
+
+fn save_crash(target_name: &str, data: &[u8]) -> Result<PathBuf> {
+    let directory = Path::new("artifacts").join(target_name);
+    std::fs::create_dir_all(&directory)?;
+
+    let hash = data
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100_0000_01b3)
+        });
+    let path = directory.join(format!("crash-{:016x}", hash));
+    std::fs::write(&path, data)?;
+
+    Ok(path)
+}
+
+fn seed() -> Result<()> {
+    for target_name in day15_fuzz::TARGETS.iter() {
+        let directory = Path::new("corpus").join(target_name);
+        std::fs::create_dir_all(&directory)?;
+
+        for (i, seed) in day15_fuzz::SEEDS.iter().enumerate() {
+            std::fs::copy(seed, directory.join(format!("seed-{}", i)))
+                .with_context(|| format!("Failed to copy {}", seed))?;
+        }
+
+        println!("Seeded {}", directory.display());
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.len() < 2 {
+        return Err(format_err!(
+            "Usage: {0} seed | {0} <target> [--mutate N] [--rng SEED] [PATH ...]",
+            args[0]
+        ));
+    }
+
+    if args[1] == "seed" {
+        return seed();
+    }
+
+    let target_name = args[1].as_str();
+    let target = day15_fuzz::target(target_name)
+        .ok_or(format_err!("Unknown target \"{}\"", target_name))?;
+
+    let mut mutations = 0;
+    let mut rng = Rng(0x2019_1215);
+    let mut paths = Vec::new();
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--mutate" => {
+                mutations = rest
+                    .next()
+                    .ok_or(format_err!("--mutate needs a count"))?
+                    .parse()?
+            }
+            "--rng" => {
+                rng = Rng(rest
+                    .next()
+                    .ok_or(format_err!("--rng needs a seed"))?
+                    .parse::<u64>()?
+                    .max(1))
+            }
+            path => paths.push(PathBuf::from(path)),
+        }
+    }
+
+    let mut files = Vec::new();
+    if paths.is_empty() {
+        files.extend(day15_fuzz::SEEDS.iter().map(PathBuf::from));
+        let corpus = Path::new("corpus").join(target_name);
+        if corpus.is_dir() {
+            collect_files(&corpus, &mut files)?;
+        }
+    }
+    for path in paths.iter() {
+        collect_files(path, &mut files)?;
+    }
+
+    let inputs = files
+        .iter()
+        .map(|file| std::fs::read(file).with_context(|| format!("Failed to read {}", file.display())))
+        .collect::<Result<Vec<Vec<u8>>>>()?;
+    if inputs.is_empty() {
+        return Err(format_err!("No inputs to run"));
+    }
+
+    // Panics are reported with the input that caused them instead
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut crashes = 0;
+    for (file, data) in files.iter().zip(inputs.iter()) {
+        if let Some(message) = check(target, data) {
+            println!("PANIC {}: {}", file.display(), message);
+            crashes += 1;
+        }
+    }
+
+    for _ in 0..mutations {
+        let data = mutate(&inputs[rng.below(inputs.len())], &mut rng);
+        if let Some(message) = check(target, &data) {
+            println!("PANIC {} (saved to {})", message, save_crash(target_name, &data)?.display());
+            crashes += 1;
+        }
+    }
+
+    let _ = panic::take_hook();
+
+    println!(
+        "{}: {} input(s), {} mutation(s), {} panic(s)",
+        target_name,
+        inputs.len(),
+        mutations,
+        crashes
+    );
+
+    if crashes > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
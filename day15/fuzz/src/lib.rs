@@ -0,0 +1,118 @@
+// day15 is a binary crate, so the VM is included rather than depended on.
+// Every target takes a comma separated program as text, which lets the repo's
+// puzzle inputs serve as the seed corpus. `tape` also takes binary tapes.
+
+// Keeps each run fast while still getting past the setup code of the inputs
+const MAX_STEPS: usize = 10_000;
+
+pub const TARGETS: &[&str] = &["decode", "run", "tape"];
+
+pub const SEEDS: &[&str] = &[
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../input.txt"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../day2/input.txt"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../day5/input.txt"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../day7/input.txt"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../day7/test.txt"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../day9/input.txt"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../day9/test.txt"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../day13/input.txt"),
+];
+
+pub fn target(name: &str) -> Option<fn(&[u8])> {
+    match name {
+        "decode" => Some(intcode::fuzz_decode),
+        "run" => Some(intcode::fuzz_run),
+        "tape" => Some(intcode::fuzz_tape),
+        _ => None,
+    }
+}
+
+#[allow(dead_code)]
+pub mod intcode {
+    include!("../../src/intcode.rs");
+
+    fn parse(data: &[u8]) -> Option<Tape> {
+        std::str::from_utf8(data).ok()?.parse().ok()
+    }
+
+    // Decoding must fail cleanly anywhere a jump can land
+    pub fn fuzz_decode(data: &[u8]) {
+        let tape = match parse(data) {
+            Some(tape) => tape,
+            None => return,
+        };
+
+        let end = tape.memory.keys().next_back().map_or(0, |last| last + 1);
+        for offset in (0..=end).chain(vec![i64::MAX as usize - 1, i64::MAX as usize]) {
+            if let Ok(instruction) = Instruction::new(&tape, offset) {
+                for index in 0..instruction.arguments.len() {
+                    let _ = instruction.get_argument_value(&tape, index);
+                    let _ = instruction.get_argument_value_for_set(&tape, index);
+                }
+            }
+        }
+    }
+
+    // Runs for a bounded number of instructions, answering every input with
+    // the next value from a fixed sequence
+    pub fn fuzz_run(data: &[u8]) {
+        let mut program = match parse(data) {
+            Some(tape) => Program::new(&tape),
+            None => return,
+        };
+        program.start_recording();
+        // Scattered writes to huge addresses would otherwise eat memory
+        program.set_limits(Limits {
+            max_resident_cells: Some(1 << 16),
+            ..Limits::default()
+        });
+
+        let sequence = [0, 1, -1, 2, 3, 4, i64::MAX, i64::MIN];
+        let mut inputs = VecDeque::new();
+        let mut outputs = VecDeque::new();
+        for step in 0..super::MAX_STEPS {
+            if let ProgramState::Terminated = program.state {
+                return;
+            }
+            if inputs.is_empty() {
+                inputs.push_back(sequence[step % sequence.len()]);
+            }
+
+            let instruction = match Instruction::new(&program.tape, program.pc) {
+                Ok(instruction) => instruction,
+                Err(_) => return,
+            };
+            if program
+                .execute(&instruction, &mut inputs, &mut outputs)
+                .is_err()
+            {
+                return;
+            }
+            outputs.clear();
+        }
+    }
+
+    // Binary tapes must fail cleanly, and anything that loads in either
+    // format must survive a trip through both
+    pub fn fuzz_tape(data: &[u8]) {
+        let tape = match Tape::from_bytes(data) {
+            Ok(tape) => tape,
+            Err(_) => match parse(data) {
+                Some(tape) => tape,
+                None => return,
+            },
+        };
+        let values = tape.values();
+
+        let decoded = Tape::from_bytes(&intcode_tape::encode(&values))
+            .expect("Encoded tape doesn't decode");
+        assert_eq!(decoded.values(), values);
+
+        let parsed: Tape = tape.to_text().parse().expect("Tape text doesn't parse");
+        assert_eq!(parsed.values(), values);
+    }
+}
+
+pub mod recording {
+    include!("../../../common/recording.rs");
+}
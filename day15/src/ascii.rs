@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+use std::io::{stdin, stdout, BufRead, Write};
+
+use anyhow::{format_err, Result};
+
+use crate::intcode::{Program, ProgramState};
+
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    Line(String),
+    // Anything outside the ASCII range, e.g. a final answer
+    Value(i64),
+}
+
+// Wraps a program that talks in ASCII: inputs are sent as lines of text and
+// outputs are collected back into lines
+pub struct AsciiProgram {
+    program: Program,
+    partial: String,
+}
+
+impl AsciiProgram {
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            partial: String::new(),
+        }
+    }
+
+    pub fn encode(text: &str) -> Result<VecDeque<i64>> {
+        text.chars()
+            .map(|c| {
+                if c.is_ascii() {
+                    Ok(c as i64)
+                } else {
+                    Err(format_err!("Can't send non-ASCII character {:?}", c))
+                }
+            })
+            .collect()
+    }
+
+    fn decode(&mut self, outputs: VecDeque<i64>) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        for output in outputs {
+            match output {
+                10 => events.push(Event::Line(std::mem::take(&mut self.partial))),
+                0..=127 => self.partial.push(output as u8 as char),
+                _ => events.push(Event::Value(output)),
+            }
+        }
+
+        events
+    }
+
+    // Runs until the program wants input or terminates. A line still being
+    // written when the program terminates is returned as well.
+    pub fn run(&mut self) -> Result<Vec<Event>> {
+        self.send("")
+    }
+
+    // Sends `line` followed by a newline and runs until the program wants more
+    pub fn send_line(&mut self, line: &str) -> Result<Vec<Event>> {
+        self.send(&format!("{}\n", line))
+    }
+
+    fn send(&mut self, text: &str) -> Result<Vec<Event>> {
+        let mut inputs = Self::encode(text)?;
+        let outputs = self.program.run_to_next_input(&mut inputs)?;
+        let mut events = self.decode(outputs);
+
+        if self.is_terminated() && !self.partial.is_empty() {
+            events.push(Event::Line(std::mem::take(&mut self.partial)));
+        }
+
+        Ok(events)
+    }
+
+    // Text output so far on a line the program hasn't finished, like a prompt
+    pub fn partial_line(&self) -> &str {
+        &self.partial
+    }
+
+    pub fn is_terminated(&self) -> bool {
+        matches!(self.program.get_state(), ProgramState::Terminated)
+    }
+
+    // Prints everything the program says and feeds it lines typed on stdin
+    // until it terminates or stdin runs out
+    pub fn interactive(&mut self) -> Result<()> {
+        let mut events = self.run()?;
+        let mut lines = stdin().lock().lines();
+
+        loop {
+            for event in events {
+                match event {
+                    Event::Line(line) => println!("{}", line),
+                    Event::Value(value) => println!("[VALUE] {}", value),
+                }
+            }
+
+            if self.is_terminated() {
+                return Ok(());
+            }
+
+            print!("{}", self.partial_line());
+            stdout().flush()?;
+
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(()),
+            };
+            events = self.send_line(&line)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_and_values() -> Result<()> {
+        // Echoes one character, prints "Hi\n", then a non-ASCII value
+        let program: Program = "3,17,4,17,104,72,104,105,104,10,104,1000,104,63,99,0,0,0"
+            .parse()
+            .map_err(|e| format_err!("{:?}", e))?;
+        let mut ascii = AsciiProgram::new(program);
+
+        assert_eq!(ascii.run()?, vec![]);
+        assert!(!ascii.is_terminated());
+
+        assert_eq!(
+            ascii.send_line("x")?,
+            vec![
+                Event::Line("xHi".to_string()),
+                Event::Value(1000),
+                Event::Line("?".to_string()),
+            ]
+        );
+        assert!(ascii.is_terminated());
+
+        Ok(())
+    }
+}
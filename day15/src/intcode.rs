@@ -1,13 +1,15 @@
 use std::collections::{BTreeMap, VecDeque};
-use std::num::ParseIntError;
 use std::convert::{TryFrom, TryInto};
 use std::str::FromStr;
 
 use anyhow::{format_err, Context, Error, Result};
-use log::{trace};
+use intcode_tape::parse_values;
+use log::trace;
+
+use super::recording::{Event, Recording};
 
 #[derive(Debug)]
-enum OpCode {
+pub(crate) enum OpCode {
     Add,
     Multiply,
     Input,
@@ -62,17 +64,60 @@ impl TryFrom<&str> for OpCode {
     }
 }
 
+// Bounds on what a program may do, for running programs nobody has vetted.
+// Anything left as None is unlimited.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    // Highest address a program may write to
+    pub max_address: Option<usize>,
+    // Most cells memory may hold, counting the program itself
+    pub max_resident_cells: Option<usize>,
+    // Most outputs a single run may produce before the caller takes them
+    pub max_outputs: Option<usize>,
+    // Most instructions a program may run over its lifetime
+    pub max_instructions: Option<u64>,
+}
+
+impl Limits {
+    // Roomy enough for every puzzle input
+    pub fn sandbox() -> Self {
+        Self {
+            max_address: Some(1 << 24),
+            max_resident_cells: Some(1 << 20),
+            max_outputs: Some(1 << 20),
+            max_instructions: Some(1 << 32),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Clone, Debug, PartialEq)]
+pub enum LimitExceeded {
+    #[error("Write to address {address} is past the limit of {max}")]
+    Address { address: usize, max: usize },
+
+    #[error("Memory is full at {max} cell(s)")]
+    ResidentCells { max: usize },
+
+    #[error("More than {max} output(s) buffered")]
+    Outputs { max: usize },
+
+    #[error("Ran {max} instruction(s) without terminating")]
+    Instructions { max: u64 },
+}
+
 #[derive(Clone)]
 pub struct Tape {
-    memory: BTreeMap<usize, i64>,
+    pub(crate) memory: BTreeMap<usize, i64>,
     relative_base: i64,
+    limits: Limits,
 }
 
 impl Tape {
-    fn new(program: &Vec<i64>) -> Self {
+    pub(crate) fn new(program: &[i64]) -> Self {
         let mut tape = Tape {
             memory: BTreeMap::new(),
             relative_base: 0,
+            limits: Limits::default(),
         };
         for (i, item) in program.iter().enumerate() {
             tape.memory.insert(i, *item);
@@ -81,13 +126,29 @@ impl Tape {
         tape
     }
 
-    fn get(&self, offset: usize) -> Option<i64> {
+    pub(crate) fn get(&self, offset: usize) -> Option<i64> {
         self.memory.get(&offset).or(Some(&0)).cloned()
     }
 
     fn set(&mut self, offset: usize, value: i64) -> Result<()> {
         trace!("[SET] [{}] = {}", offset, value);
 
+        if let Some(max) = self.limits.max_address {
+            if offset > max {
+                return Err(LimitExceeded::Address {
+                    address: offset,
+                    max,
+                }
+                .into());
+            }
+        }
+
+        if let Some(max) = self.limits.max_resident_cells {
+            if self.memory.len() >= max && !self.memory.contains_key(&offset) {
+                return Err(LimitExceeded::ResidentCells { max }.into());
+            }
+        }
+
         self.memory.insert(offset, value);
 
         Ok(())
@@ -97,27 +158,66 @@ impl Tape {
         self.relative_base
     }
 
-    fn set_relative_base(&mut self, new_base: i64) {
+    pub(crate) fn set_relative_base(&mut self, new_base: i64) {
         self.relative_base = new_base;
     }
 }
 
 impl FromStr for Tape {
-    type Err = ParseIntError;
+    type Err = Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut ret = Vec::new();
+        Ok(Tape::new(&parse_values(input)?))
+    }
+}
 
-        for num in input.trim().split(",") {
-            ret.push(num.parse()?);
+impl Tape {
+    // Number of values up to and including the last one set
+    pub fn len(&self) -> usize {
+        self.memory.keys().next_back().map_or(0, |last| last + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn values(&self) -> Vec<i64> {
+        (0..self.len())
+            .map(|offset| self.get(offset).unwrap_or(0))
+            .collect()
+    }
+
+    pub fn to_text(&self) -> String {
+        intcode_tape::to_text(&self.values())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(Tape::new(&intcode_tape::decode(bytes)?))
+    }
+
+    // Loads either format, telling them apart by the binary header
+    pub fn from_file(filename: &str) -> Result<Self> {
+        let bytes =
+            std::fs::read(filename).with_context(|| format!("Failed to read {}", filename))?;
+
+        let tape: Self = if bytes.starts_with(intcode_tape::MAGIC) {
+            Self::from_bytes(&bytes).with_context(|| format!("Bad binary tape {}", filename))?
+        } else {
+            std::str::from_utf8(&bytes)?
+                .parse()
+                .with_context(|| format!("Bad text tape {}", filename))?
+        };
+
+        if tape.is_empty() {
+            return Err(format_err!("{} holds no program", filename));
         }
 
-        Ok(Tape::new(&ret))
+        Ok(tape)
     }
 }
 
 #[derive(Debug)]
-enum FetchMode {
+pub(crate) enum FetchMode {
     Immediate,
     Position,
     Relative,
@@ -136,38 +236,48 @@ impl TryFrom<char> for FetchMode {
     }
 }
 
+fn to_address(value: i64) -> Result<usize> {
+    usize::try_from(value).map_err(|_| format_err!("Negative address {}", value))
+}
+
 #[derive(Debug)]
-struct Argument {
-    mode: FetchMode,
-    value: i64,
+pub(crate) struct Argument {
+    pub(crate) mode: FetchMode,
+    pub(crate) value: i64,
 }
 
 impl Argument {
     fn get(&self, tape: &Tape, relative_base: i64) -> Option<i64> {
         match self.mode {
             FetchMode::Immediate => Some(self.value),
-            FetchMode::Position => tape.get(self.value as usize),
-            FetchMode::Relative => tape.get((self.value + relative_base) as usize),
+            FetchMode::Position => to_address(self.value)
+                .ok()
+                .and_then(|address| tape.get(address)),
+            FetchMode::Relative => self
+                .value
+                .checked_add(relative_base)
+                .and_then(|address| to_address(address).ok())
+                .and_then(|address| tape.get(address)),
         }
     }
 
-    fn get_for_set(&self, relative_base: i64) -> i64 {
+    fn get_for_set(&self, relative_base: i64) -> Option<i64> {
         match self.mode {
-            FetchMode::Relative => self.value + relative_base,
-            _ => self.value,
+            FetchMode::Relative => self.value.checked_add(relative_base),
+            _ => Some(self.value),
         }
     }
 }
 
 #[derive(Debug)]
-struct Instruction {
+pub(crate) struct Instruction {
     position: usize,
-    opcode: OpCode,
-    arguments: Vec<Argument>,
+    pub(crate) opcode: OpCode,
+    pub(crate) arguments: Vec<Argument>,
 }
 
 impl Instruction {
-    fn new(tape: &Tape, offset: usize) -> Result<Self> {
+    pub(crate) fn new(tape: &Tape, offset: usize) -> Result<Self> {
         let code = format!(
             "{:0>2}",
             tape.get(offset)
@@ -193,7 +303,11 @@ impl Instruction {
                 mode: c
                     .try_into()
                     .with_context(|| format!("Failed to parse mode \"{}\"", c))?,
-                value: tape.get(offset + i + 1).unwrap(),
+                value: tape.get(offset + i + 1).ok_or(format_err!(
+                    "Missing argument {} for instruction at offset {}",
+                    i + 1,
+                    offset
+                ))?,
             })
         }
 
@@ -242,13 +356,19 @@ impl Instruction {
             ))
     }
 
-    fn get_argument_value_for_set(&self, tape: &Tape, index: usize) -> Result<i64> {
-        Ok(self
+    fn get_argument_value_for_set(&self, tape: &Tape, index: usize) -> Result<usize> {
+        let address = self
             .get_argument(index)?
-            .get_for_set(tape.get_relative_base()))
+            .get_for_set(tape.get_relative_base())
+            .ok_or(format_err!(
+                "Argument {} for opcode {:?} overflowed",
+                index + 1,
+                self.opcode
+            ))?;
+        to_address(address)
     }
 
-    fn run(
+    pub(crate) fn run(
         &self,
         tape: &mut Tape,
         inputs: &mut VecDeque<i64>,
@@ -261,7 +381,11 @@ impl Instruction {
                 let arg1 = self.get_argument_value(tape, 0)?;
                 let arg2 = self.get_argument_value(tape, 1)?;
                 let result_offset = self.get_argument_value_for_set(tape, 2)?;
-                let result = arg1 + arg2;
+                let result = arg1.checked_add(arg2).ok_or(format_err!(
+                    "Overflow adding {} and {}",
+                    arg1,
+                    arg2
+                ))?;
 
                 trace!(
                     "[ADD] {} + {} = {}, [{}]",
@@ -271,7 +395,7 @@ impl Instruction {
                     result_offset
                 );
 
-                tape.set(result_offset as usize, result).with_context(|| {
+                tape.set(result_offset, result).with_context(|| {
                     format!(
                         "Failed to set multiplied value {} to tape index {}",
                         result, result_offset
@@ -288,7 +412,11 @@ impl Instruction {
                 let arg2 = self.get_argument_value(tape, 1)?;
                 let result_offset = self.get_argument_value_for_set(tape, 2)?;
 
-                let result = arg1 * arg2;
+                let result = arg1.checked_mul(arg2).ok_or(format_err!(
+                    "Overflow multiplying {} and {}",
+                    arg1,
+                    arg2
+                ))?;
 
                 trace!(
                     "[MUL] {} * {} = {}, [{}]",
@@ -298,7 +426,7 @@ impl Instruction {
                     result_offset
                 );
 
-                tape.set(result_offset as usize, result).with_context(|| {
+                tape.set(result_offset, result).with_context(|| {
                     format!(
                         "Failed to set multiplied value {} to tape index {}",
                         result, result_offset
@@ -318,7 +446,7 @@ impl Instruction {
 
                 trace!("[INP] {} -> [{}]", value, result_offset);
 
-                tape.set(result_offset as usize, value).with_context(|| {
+                tape.set(result_offset, value).with_context(|| {
                     format!(
                         "Failed to set input value {} to tape index {}",
                         value, result_offset
@@ -346,7 +474,7 @@ impl Instruction {
                     next_offset: if arg1 == 0 {
                         default_next_offset
                     } else {
-                        arg2 as usize
+                        to_address(arg2)?
                     },
                     relative_base: tape.get_relative_base(),
                 })
@@ -357,7 +485,7 @@ impl Instruction {
 
                 Ok(InstructionResult::Continue {
                     next_offset: if arg1 == 0 {
-                        arg2 as usize
+                        to_address(arg2)?
                     } else {
                         default_next_offset
                     },
@@ -371,7 +499,7 @@ impl Instruction {
 
                 let value = if arg1 < arg2 { 1 } else { 0 };
 
-                tape.set(result_offset as usize, value).with_context(|| {
+                tape.set(result_offset, value).with_context(|| {
                     format!(
                         "Failed to set less than value {} to tape index {}",
                         value, result_offset
@@ -390,7 +518,7 @@ impl Instruction {
 
                 let value = if arg1 == arg2 { 1 } else { 0 };
 
-                tape.set(result_offset as usize, value).with_context(|| {
+                tape.set(result_offset, value).with_context(|| {
                     format!(
                         "Failed to set less than value {} to tape index {}",
                         value, result_offset
@@ -404,10 +532,17 @@ impl Instruction {
             }
             OpCode::AdjustRelativeBase => {
                 let arg = self.get_argument_value(tape, 0)?;
-                let relative_base = tape.get_relative_base() + arg;
+                let relative_base =
+                    tape.get_relative_base()
+                        .checked_add(arg)
+                        .ok_or(format_err!(
+                            "Overflow adjusting relative base {} by {}",
+                            tape.get_relative_base(),
+                            arg
+                        ))?;
                 Ok(InstructionResult::Continue {
                     next_offset: default_next_offset,
-                    relative_base: relative_base,
+                    relative_base,
                 })
             }
             OpCode::Terminate => Ok(InstructionResult::Terminate),
@@ -415,7 +550,7 @@ impl Instruction {
     }
 }
 
-enum InstructionResult {
+pub(crate) enum InstructionResult {
     Continue {
         next_offset: usize,
         relative_base: i64,
@@ -432,6 +567,8 @@ pub struct Program {
     tape: Tape,
     pc: usize,
     state: ProgramState,
+    instruction_count: u64,
+    recording: Option<Recording>,
 }
 
 impl Program {
@@ -440,19 +577,83 @@ impl Program {
             tape: tape.clone(),
             pc: 0,
             state: ProgramState::Running,
+            instruction_count: 0,
+            recording: None,
         }
     }
 
     pub fn from_file(filename: &str) -> Result<Self> {
-        let input = std::fs::read_to_string(filename)?;
+        Ok(Self::new(&Tape::from_file(filename)?))
+    }
+
+    fn execute(
+        &mut self,
+        instruction: &Instruction,
+        inputs: &mut VecDeque<i64>,
+        outputs: &mut VecDeque<i64>,
+    ) -> Result<InstructionResult> {
+        if let Some(max) = self.tape.limits.max_instructions {
+            if self.instruction_count >= max {
+                return Err(LimitExceeded::Instructions { max }.into());
+            }
+        }
+
+        let input = inputs.front().cloned();
+        let inputs_len = inputs.len();
+        let outputs_len = outputs.len();
+
+        let result = instruction
+            .run(&mut self.tape, inputs, outputs)
+            .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?;
+
+        if let Some(max) = self.tape.limits.max_outputs {
+            if outputs.len() > max {
+                return Err(LimitExceeded::Outputs { max }.into());
+            }
+        }
 
-        let mut ret = Vec::new();
+        match result {
+            InstructionResult::Continue {
+                next_offset,
+                relative_base,
+            } => {
+                self.pc = next_offset;
+                self.tape.set_relative_base(relative_base);
+            }
+            InstructionResult::Terminate => {
+                self.state = ProgramState::Terminated;
+            }
+        }
 
-        for num in input.trim().split(",").filter(|l| l.len() > 0) {
-            ret.push(num.parse()?);
+        let input = if inputs.len() < inputs_len {
+            input
+        } else {
+            None
+        };
+        let output = if outputs.len() > outputs_len {
+            outputs.back().cloned()
+        } else {
+            None
+        };
+
+        if let Some(recording) = self.recording.as_mut() {
+            if let Some(value) = input {
+                recording.push(Event::Input {
+                    instruction: self.instruction_count,
+                    value,
+                });
+            }
+            if let Some(value) = output {
+                recording.push(Event::Output {
+                    instruction: self.instruction_count,
+                    value,
+                });
+            }
         }
 
-        Ok(Self::new(&Tape::new(&ret)))
+        self.instruction_count += 1;
+
+        Ok(result)
     }
 
     pub fn run_to_next_output(&mut self, inputs: &mut VecDeque<i64>) -> Result<Option<i64>> {
@@ -464,24 +665,13 @@ impl Program {
             let instruction = Instruction::new(&self.tape, self.pc)
                 .with_context(|| format!("Failed to build instruction at offset {}", self.pc))?;
 
-            match instruction
-                .run(&mut self.tape, inputs, &mut outputs)
-                .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?
-            {
-                InstructionResult::Continue {
-                    next_offset,
-                    relative_base,
-                } => {
-                    self.pc = next_offset;
-                    self.tape.set_relative_base(relative_base);
+            match self.execute(&instruction, inputs, &mut outputs)? {
+                InstructionResult::Continue { .. } => {
                     if outputs.len() > starting_len {
                         break;
                     }
                 }
-                InstructionResult::Terminate => {
-                    self.state = ProgramState::Terminated;
-                    break;
-                }
+                InstructionResult::Terminate => break,
             }
 
             instruction_count += 1;
@@ -501,26 +691,15 @@ impl Program {
                 .with_context(|| format!("Failed to build instruction at offset {}", self.pc))?;
 
             if let OpCode::Input = instruction.opcode {
-                if inputs.len() == 0 {
+                if inputs.is_empty() {
                     break;
                 }
             }
 
-            match instruction
-                .run(&mut self.tape, inputs, &mut outputs)
-                .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?
+            if let InstructionResult::Terminate =
+                self.execute(&instruction, inputs, &mut outputs)?
             {
-                InstructionResult::Continue {
-                    next_offset,
-                    relative_base,
-                } => {
-                    self.pc = next_offset;
-                    self.tape.set_relative_base(relative_base);
-                }
-                InstructionResult::Terminate => {
-                    self.state = ProgramState::Terminated;
-                    break;
-                }
+                break;
             }
 
             instruction_count += 1;
@@ -531,6 +710,8 @@ impl Program {
         Ok(outputs.clone())
     }
 
+    // Runs to termination. Only the tests need a program run in one go.
+    #[cfg(test)]
     pub fn run(&mut self, inputs: &mut VecDeque<i64>) -> Result<VecDeque<i64>> {
         // TODO(jsvana): make this not duplicated
         let mut outputs = VecDeque::new();
@@ -539,48 +720,40 @@ impl Program {
             let instruction = Instruction::new(&self.tape, self.pc)
                 .with_context(|| format!("Failed to build instruction at offset {}", self.pc))?;
 
-            match instruction
-                .run(&mut self.tape, inputs, &mut outputs)
-                .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?
+            if let InstructionResult::Terminate =
+                self.execute(&instruction, inputs, &mut outputs)?
             {
-                InstructionResult::Continue {
-                    next_offset,
-                    relative_base,
-                } => {
-                    self.pc = next_offset;
-                    self.tape.set_relative_base(relative_base);
-                }
-                InstructionResult::Terminate => {
-                    self.state = ProgramState::Terminated;
-                    break;
-                }
+                break;
             }
         }
 
         Ok(outputs)
     }
 
-    pub fn get_state(&self) -> &ProgramState {
-        &self.state
+    // Captures every input consumed and output produced from here on
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new());
     }
 
-    pub fn set_memory_value(&mut self, location: usize, value: i64) -> Result<()> {
-        self.tape.set(location, value)?;
+    pub fn get_recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
 
-        Ok(())
+    // Exceeding any of these fails the offending instruction with a
+    // `LimitExceeded`, which callers can get back with `downcast_ref`
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.tape.limits = limits;
+    }
+
+    pub fn get_state(&self) -> &ProgramState {
+        &self.state
     }
 }
 
 impl FromStr for Program {
-    type Err = ParseIntError;
+    type Err = Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let mut ret = Vec::new();
-
-        for num in input.trim().split(",") {
-            ret.push(num.parse()?);
-        }
-
-        Ok(Program::new(&Tape::new(&ret)))
+        Ok(Program::new(&input.parse()?))
     }
 }
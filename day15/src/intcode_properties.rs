@@ -0,0 +1,499 @@
+// Property tests for the VM. Random programs are assembled from a small set of
+// operations, run on `Program`, and checked against a deliberately simple
+// reference evaluator. These live outside intcode.rs since that file is also
+// included by the fuzz targets and the differential harness.
+use std::collections::{BTreeMap, VecDeque};
+
+use proptest::prelude::*;
+
+use crate::intcode::{LimitExceeded, Limits, Program, Tape};
+use crate::optimize::{self, optimize};
+
+// Data cells the generated instructions read and write
+const CELLS: usize = 8;
+const MAX_ADJUSTMENTS: usize = 4;
+const MAX_ADJUSTMENT: i64 = 3;
+// Jumps can skip relative base adjustments, so relative operands may drift
+// this far from the cell they were assembled for
+const MARGIN: usize = MAX_ADJUSTMENTS * MAX_ADJUSTMENT as usize;
+// Programs that write over their own code can loop
+const MAX_INSTRUCTIONS: u64 = 10_000;
+
+#[derive(Clone, Debug)]
+enum Operand {
+    Position(usize),
+    Immediate(i64),
+    Relative(usize),
+    // Relative mode aimed at a cell of one of the operations instead of the
+    // data. Both indexes wrap, to the operations and to the cells of one.
+    Code(usize, usize),
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Add(Operand, Operand, Operand),
+    Multiply(Operand, Operand, Operand),
+    LessThan(Operand, Operand, Operand),
+    Equals(Operand, Operand, Operand),
+    Input(Operand),
+    Output(Operand),
+    // Skips the given number of following operations when taken
+    JumpIfTrue(Operand, usize),
+    JumpIfFalse(Operand, usize),
+    AdjustRelativeBase(i64),
+}
+
+impl Op {
+    fn len(&self) -> usize {
+        match self {
+            Op::Add(..) | Op::Multiply(..) | Op::LessThan(..) | Op::Equals(..) => 4,
+            Op::JumpIfTrue(..) | Op::JumpIfFalse(..) => 3,
+            Op::Input(_) | Op::Output(_) | Op::AdjustRelativeBase(_) => 2,
+        }
+    }
+}
+
+fn value() -> impl Strategy<Value = i64> {
+    prop_oneof![9 => -20i64..20, 1 => any::<i64>()]
+}
+
+fn operand() -> impl Strategy<Value = Operand> {
+    prop_oneof![
+        (0..CELLS).prop_map(Operand::Position),
+        value().prop_map(Operand::Immediate),
+        (0..CELLS).prop_map(Operand::Relative),
+    ]
+}
+
+fn destination() -> impl Strategy<Value = Operand> {
+    prop_oneof![
+        (0..CELLS).prop_map(Operand::Position),
+        (0..CELLS).prop_map(Operand::Relative),
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (operand(), operand(), destination()).prop_map(|(a, b, c)| Op::Add(a, b, c)),
+        (operand(), operand(), destination()).prop_map(|(a, b, c)| Op::Multiply(a, b, c)),
+        (operand(), operand(), destination()).prop_map(|(a, b, c)| Op::LessThan(a, b, c)),
+        (operand(), operand(), destination()).prop_map(|(a, b, c)| Op::Equals(a, b, c)),
+        destination().prop_map(Op::Input),
+        operand().prop_map(Op::Output),
+        (operand(), 0usize..4).prop_map(|(a, skip)| Op::JumpIfTrue(a, skip)),
+        (operand(), 0usize..4).prop_map(|(a, skip)| Op::JumpIfFalse(a, skip)),
+        (-MAX_ADJUSTMENT..=MAX_ADJUSTMENT).prop_map(Op::AdjustRelativeBase),
+    ]
+}
+
+// Writes through relative mode into the program's code
+fn code_write() -> impl Strategy<Value = Op> {
+    let code = (0usize..24, 0usize..4).prop_map(|(op, cell)| Operand::Code(op, cell));
+    prop_oneof![
+        (operand(), operand(), code.clone()).prop_map(|(a, b, c)| Op::Add(a, b, c)),
+        (operand(), operand(), code.clone()).prop_map(|(a, b, c)| Op::Multiply(a, b, c)),
+        code.prop_map(Op::Input),
+    ]
+}
+
+fn sequence(op: impl Strategy<Value = Op>) -> impl Strategy<Value = Vec<Op>> {
+    prop::collection::vec(op, 0..24).prop_filter("too many relative base adjustments", |ops| {
+        ops.iter()
+            .filter(|op| matches!(op, Op::AdjustRelativeBase(_)))
+            .count()
+            <= MAX_ADJUSTMENTS
+    })
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    sequence(op())
+}
+
+fn self_modifying_ops() -> impl Strategy<Value = Vec<Op>> {
+    sequence(prop_oneof![4 => op(), 1 => code_write()])
+}
+
+// Lays out the operations, an epilogue that outputs every data cell, and the
+// data region. Jumps only go forward, so every program terminates unless it
+// writes over its own code.
+fn assemble(ops: &[Op], data: &[i64]) -> Vec<i64> {
+    let mut addresses = Vec::new();
+    let mut address = 0;
+    for op in ops.iter() {
+        addresses.push(address);
+        address += op.len();
+    }
+    let epilogue = address;
+    let cells = epilogue + CELLS * 2 + 1 + MARGIN;
+
+    let mut program = Vec::new();
+    let mut relative_base = 0;
+    for (i, op) in ops.iter().enumerate() {
+        let encode = |operand: &Operand, mode_digit: i64, program: &mut Vec<i64>| -> i64 {
+            let (mode, value) = match operand {
+                Operand::Position(cell) => (0, (cells + cell) as i64),
+                Operand::Immediate(value) => (1, *value),
+                Operand::Relative(cell) => (2, (cells + cell) as i64 - relative_base),
+                Operand::Code(op, cell) => {
+                    let op = op % ops.len();
+                    let address = addresses[op] + cell % ops[op].len();
+                    (2, address as i64 - relative_base)
+                }
+            };
+            program.push(value);
+            mode * mode_digit
+        };
+        let target = |skip: usize| match addresses.get(i + 1 + skip) {
+            Some(address) => *address as i64,
+            None => epilogue as i64,
+        };
+
+        let start = program.len();
+        program.push(0);
+        let code = match op {
+            Op::Add(a, b, c)
+            | Op::Multiply(a, b, c)
+            | Op::LessThan(a, b, c)
+            | Op::Equals(a, b, c) => {
+                let opcode = match op {
+                    Op::Add(..) => 1,
+                    Op::Multiply(..) => 2,
+                    Op::LessThan(..) => 7,
+                    _ => 8,
+                };
+                opcode
+                    + encode(a, 100, &mut program)
+                    + encode(b, 1000, &mut program)
+                    + encode(c, 10000, &mut program)
+            }
+            Op::Input(a) => 3 + encode(a, 100, &mut program),
+            Op::Output(a) => 4 + encode(a, 100, &mut program),
+            Op::JumpIfTrue(a, skip) | Op::JumpIfFalse(a, skip) => {
+                let opcode = if let Op::JumpIfTrue(..) = op { 5 } else { 6 };
+                let code = opcode + encode(a, 100, &mut program) + 1000;
+                program.push(target(*skip));
+                code
+            }
+            Op::AdjustRelativeBase(delta) => {
+                relative_base += delta;
+                program.push(*delta);
+                109
+            }
+        };
+        program[start] = code;
+    }
+
+    for cell in 0..CELLS {
+        program.push(4);
+        program.push((cells + cell) as i64);
+    }
+    program.push(99);
+
+    program.resize(cells, 0);
+    program.extend(data.iter());
+    program.resize(cells + CELLS + MARGIN, 0);
+
+    program
+}
+
+// Straightforward evaluator with none of the VM's decoding machinery. Returns
+// None wherever the VM should fail.
+fn evaluate(program: &[i64], inputs: &[i64]) -> Option<Vec<i64>> {
+    let mut memory: BTreeMap<usize, i64> = program.iter().cloned().enumerate().collect();
+    let mut inputs = inputs.iter();
+    let mut outputs = Vec::new();
+    let mut pc = 0;
+    let mut relative_base = 0i64;
+
+    loop {
+        let code = *memory.get(&pc).unwrap_or(&0);
+        let operand = |n: usize| *memory.get(&(pc + n)).unwrap_or(&0);
+        let mode = |n: usize| (code / 10i64.pow(n as u32 + 1)) % 10;
+        let address = |n: usize| -> Option<usize> {
+            let address = match mode(n) {
+                0 => operand(n),
+                2 => relative_base.checked_add(operand(n))?,
+                _ => return None,
+            };
+            if address < 0 {
+                None
+            } else {
+                Some(address as usize)
+            }
+        };
+        let read = |n: usize| -> Option<i64> {
+            match mode(n) {
+                1 => Some(operand(n)),
+                _ => Some(*memory.get(&address(n)?).unwrap_or(&0)),
+            }
+        };
+
+        match code % 100 {
+            1 | 2 | 7 | 8 => {
+                let (a, b) = (read(1)?, read(2)?);
+                let result = match code % 100 {
+                    1 => a.checked_add(b)?,
+                    2 => a.checked_mul(b)?,
+                    7 => (a < b) as i64,
+                    _ => (a == b) as i64,
+                };
+                let destination = address(3)?;
+                memory.insert(destination, result);
+                pc += 4;
+            }
+            3 => {
+                let destination = address(1)?;
+                memory.insert(destination, *inputs.next()?);
+                pc += 2;
+            }
+            4 => {
+                outputs.push(read(1)?);
+                pc += 2;
+            }
+            5 | 6 => {
+                let (condition, target) = (read(1)?, read(2)?);
+                if (condition != 0) == (code % 100 == 5) {
+                    if target < 0 {
+                        return None;
+                    }
+                    pc = target as usize;
+                } else {
+                    pc += 3;
+                }
+            }
+            9 => {
+                relative_base = relative_base.checked_add(read(1)?)?;
+                pc += 2;
+            }
+            99 => return Some(outputs),
+            _ => return None,
+        }
+    }
+}
+
+// The optimizer leaves regions that use relative mode as they are, so this
+// swaps it for position mode to give it more to rewrite
+fn without_relative(ops: &[Op]) -> Vec<Op> {
+    let position = |operand: &Operand| match operand {
+        Operand::Relative(cell) => Operand::Position(*cell),
+        operand => operand.clone(),
+    };
+
+    ops.iter()
+        .filter(|op| !matches!(op, Op::AdjustRelativeBase(_)))
+        .map(|op| match op {
+            Op::Add(a, b, c) => Op::Add(position(a), position(b), position(c)),
+            Op::Multiply(a, b, c) => Op::Multiply(position(a), position(b), position(c)),
+            Op::LessThan(a, b, c) => Op::LessThan(position(a), position(b), position(c)),
+            Op::Equals(a, b, c) => Op::Equals(position(a), position(b), position(c)),
+            Op::Input(a) => Op::Input(position(a)),
+            Op::Output(a) => Op::Output(position(a)),
+            Op::JumpIfTrue(a, skip) => Op::JumpIfTrue(position(a), *skip),
+            Op::JumpIfFalse(a, skip) => Op::JumpIfFalse(position(a), *skip),
+            op => op.clone(),
+        })
+        .collect()
+}
+
+fn run(program: &[i64], inputs: &[i64]) -> Option<Vec<i64>> {
+    let source: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+    let mut program: Program = source.join(",").parse().ok()?;
+    let mut inputs: VecDeque<i64> = inputs.iter().cloned().collect();
+    program.set_limits(Limits {
+        max_instructions: Some(MAX_INSTRUCTIONS),
+        ..Limits::default()
+    });
+
+    program
+        .run(&mut inputs)
+        .ok()
+        .map(|outputs| outputs.into_iter().collect())
+}
+
+proptest! {
+    #[test]
+    fn matches_reference(
+        ops in ops(),
+        data in prop::collection::vec(value(), CELLS),
+        inputs in prop::collection::vec(value(), 0..4),
+    ) {
+        let program = assemble(&ops, &data);
+        prop_assert_eq!(run(&program, &inputs), evaluate(&program, &inputs));
+    }
+
+    // Straight-line arithmetic never fails, so the results can be checked
+    // against the data directly
+    #[test]
+    fn arithmetic(a in -1000i64..1000, b in -1000i64..1000, modes in 0usize..9) {
+        let operand = |index: usize, mode: usize| match mode {
+            0 => Operand::Position(index),
+            1 => Operand::Immediate(if index == 0 { a } else { b }),
+            _ => Operand::Relative(index),
+        };
+        let (first, second) = (operand(0, modes / 3), operand(1, modes % 3));
+        let ops = [
+            Op::Add(first.clone(), second.clone(), Operand::Position(2)),
+            Op::Multiply(first.clone(), second.clone(), Operand::Relative(3)),
+            Op::LessThan(first.clone(), second.clone(), Operand::Position(4)),
+            Op::Equals(first, second, Operand::Relative(5)),
+        ];
+        let mut data = [0; CELLS];
+        data[0] = a;
+        data[1] = b;
+
+        let outputs = run(&assemble(&ops, &data), &[]).expect("Program failed");
+        prop_assert_eq!(
+            &outputs[..6],
+            &[a, b, a + b, a * b, (a < b) as i64, (a == b) as i64][..]
+        );
+    }
+
+    // Writes past the memory limits fail with a typed error instead of
+    // growing memory
+    #[test]
+    fn memory_limits(address in prop_oneof![7usize..32, 1usize << 19..1 << 21], value in value()) {
+        // Stores `value` at `address` and outputs it back. Memory starts out
+        // full, so only the three spare cells at the end can be written.
+        let source = format!("1101,0,{},{},4,{},99,0,0,0", value, address, address);
+        let mut program: Program = source.parse().unwrap();
+        program.set_limits(Limits {
+            max_address: Some(1 << 20),
+            max_resident_cells: Some(10),
+            ..Limits::default()
+        });
+
+        let expected = if address > 1 << 20 {
+            Some(LimitExceeded::Address { address, max: 1 << 20 })
+        } else if address >= 10 {
+            Some(LimitExceeded::ResidentCells { max: 10 })
+        } else {
+            None
+        };
+
+        match program.run(&mut VecDeque::new()) {
+            Ok(outputs) => {
+                prop_assert_eq!(expected, None);
+                prop_assert_eq!(outputs, vec![value]);
+            }
+            Err(e) => prop_assert_eq!(e.downcast_ref::<LimitExceeded>(), expected.as_ref()),
+        }
+    }
+
+    // An endless output loop trips whichever limit it reaches first. Each
+    // pass is two instructions and one output.
+    #[test]
+    fn run_limits(max_outputs in 1usize..100, max_instructions in 1u64..200) {
+        let mut program: Program = "104,7,1105,1,0".parse().unwrap();
+        program.set_limits(Limits {
+            max_outputs: Some(max_outputs),
+            max_instructions: Some(max_instructions),
+            ..Limits::default()
+        });
+
+        let expected = if max_instructions <= 2 * max_outputs as u64 {
+            LimitExceeded::Instructions { max: max_instructions }
+        } else {
+            LimitExceeded::Outputs { max: max_outputs }
+        };
+        let error = program.run(&mut VecDeque::new()).unwrap_err();
+        prop_assert_eq!(error.downcast_ref::<LimitExceeded>(), Some(&expected));
+    }
+
+    #[test]
+    fn optimized_matches(
+        ops in ops(),
+        data in prop::collection::vec(value(), CELLS),
+        inputs in prop::collection::vec(value(), 0..4),
+    ) {
+        let program = assemble(&without_relative(&ops), &data);
+        let source: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+        let tape: Tape = source.join(",").parse().unwrap();
+
+        let optimized = optimize(&tape).to_tape().unwrap();
+        prop_assert_eq!(run(&optimized.values(), &inputs), run(&program, &inputs));
+    }
+
+    // Without jumps or relative mode nothing stands in the optimizer's way,
+    // so every sum or product of two immediates that fits gets folded
+    #[test]
+    fn optimized_folds_straight_line(
+        ops in ops(),
+        data in prop::collection::vec(value(), CELLS),
+        inputs in prop::collection::vec(value(), 0..4),
+    ) {
+        let ops: Vec<Op> = without_relative(&ops)
+            .into_iter()
+            .filter(|op| !matches!(op, Op::JumpIfTrue(..) | Op::JumpIfFalse(..)))
+            .collect();
+        let program = assemble(&ops, &data);
+        let source: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+        let tape: Tape = source.join(",").parse().unwrap();
+        let optimized = optimize(&tape);
+
+        let mut address = 0;
+        for op in ops.iter() {
+            let folds = match op {
+                Op::Add(Operand::Immediate(a), Operand::Immediate(b), _) => a.checked_add(*b),
+                Op::Multiply(Operand::Immediate(a), Operand::Immediate(b), _) => a.checked_mul(*b),
+                _ => None,
+            };
+            if let Some(value) = folds {
+                prop_assert!(
+                    matches!(optimized.lines[&address].op, optimize::Op::Set(v, _) if v == value),
+                    "{} at {} wasn't folded",
+                    optimized.lines[&address].original,
+                    address
+                );
+            }
+            address += op.len();
+        }
+
+        let rewritten = optimized.to_tape().unwrap();
+        prop_assert_eq!(run(&rewritten.values(), &inputs), run(&program, &inputs));
+    }
+
+    // Relative mode writes may land anywhere, including the program's own
+    // code, so whatever is still rewritten must not change the result
+    #[test]
+    fn optimized_matches_with_relative(
+        ops in self_modifying_ops(),
+        data in prop::collection::vec(value(), CELLS),
+        inputs in prop::collection::vec(value(), 0..4),
+    ) {
+        let program = assemble(&ops, &data);
+        let source: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+        let tape: Tape = source.join(",").parse().unwrap();
+
+        let optimized = optimize(&tape).to_tape().unwrap();
+        prop_assert_eq!(run(&optimized.values(), &inputs), run(&program, &inputs));
+    }
+
+    #[test]
+    fn binary_tape_round_trip(values in prop::collection::vec(any::<i64>(), 0..64)) {
+        let source: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        let tape: Tape = source.join(",").parse().unwrap();
+
+        let decoded = Tape::from_bytes(&intcode_tape::encode(&tape.values())).unwrap();
+        prop_assert_eq!(decoded.values(), values.clone());
+        prop_assert_eq!(decoded.to_text(), format!("{}\n", source.join(",")));
+    }
+
+    // Separators, blank lines and comments never change the values
+    #[test]
+    fn text_layout(
+        values in prop::collection::vec(value(), 1..16),
+        separators in prop::collection::vec(
+            prop_oneof![Just(","), Just(", "), Just(" "), Just(",\n"), Just("\n\n"), Just(" # note\n")],
+            16,
+        ),
+    ) {
+        let mut source = String::from("# header\n");
+        for (value, separator) in values.iter().zip(separators.iter()) {
+            source.push_str(&value.to_string());
+            source.push_str(separator);
+        }
+
+        let tape: Tape = source.parse().unwrap();
+        prop_assert_eq!(tape.values(), values);
+    }
+}
@@ -1,82 +1,36 @@
+mod ascii;
 mod intcode;
+#[cfg(test)]
+mod intcode_properties;
+mod maze;
+mod optimize;
 mod point;
+#[path = "../../common/recording.rs"]
+mod recording;
+#[path = "../../common/robot.rs"]
+mod robot;
 
 use std::cmp::{max, min};
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::convert::{TryFrom, TryInto};
-use std::fmt;
 
 use anyhow::{format_err, Error, Result};
 use log::debug;
 
-use intcode::Program;
+use ascii::AsciiProgram;
+use intcode::{Limits, Program, Tape};
+use maze::Maze;
+use optimize::optimize;
 use point::Point;
+use recording::{replay, Recording};
+use robot::{Heading, Pose, Protocol, Robot, World};
 
-// TODO(jsvana): Make a generic?
-struct Map {
-    data: BTreeMap<i64, BTreeMap<i64, Tile>>,
-}
-
-impl Map {
-    fn new() -> Self {
-        Self {
-            data: BTreeMap::new(),
-        }
-    }
-
-    fn set_point(&mut self, point: &Point, tile: &Tile) {
-        self.data
-            .entry(point.y)
-            .or_insert(BTreeMap::new())
-            .insert(point.x, tile.clone());
-    }
-
-    fn get_point(&self, point: &Point) -> Tile {
-        self.data
-            .get(&point.y)
-            .unwrap_or(&BTreeMap::new())
-            .get(&point.x)
-            .unwrap_or(&Tile::Unknown)
-            .clone()
-    }
-
-    fn to_string(&self, robot_position: &Point) -> String {
-        let mut top_left = Point::max();
-        let mut bottom_right = Point::min();
-
-        for (y, row) in self.data.iter() {
-            for x in row.keys() {
-                top_left.x = min(top_left.x, *x);
-                top_left.y = min(top_left.y, *y);
-                bottom_right.x = max(bottom_right.x, *x);
-                bottom_right.y = max(bottom_right.y, *y);
-            }
-        }
-
-        let row_size = bottom_right.x - top_left.x + 1;
-
-        let mut rows = Vec::new();
-        for y in top_left.y..bottom_right.y + 1 {
-            let mut row = String::with_capacity(row_size as usize);
-            for x in top_left.x..bottom_right.x + 1 {
-                if robot_position.x == x && robot_position.y == y {
-                    row.push('R');
-                } else {
-                    row.push(self.get_point(&Point { x, y }).into());
-                }
-            }
-            rows.push(row);
-        }
-
-        rows.join("\n")
-    }
-}
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 enum Tile {
     Floor,
     Wall,
     Oxygen,
+    #[default]
     Unknown,
 }
 
@@ -91,58 +45,16 @@ impl From<&Tile> for char {
     }
 }
 
-impl Into<char> for Tile {
-    fn into(self) -> char {
-        match self {
-            Tile::Floor => '.',
-            Tile::Wall => '#',
-            Tile::Oxygen => 'O',
-            Tile::Unknown => ' ',
-        }
-    }
-}
-
-#[derive(Clone, Debug, PartialEq, PartialOrd, Ord, Eq)]
-enum Direction {
-    North,
-    South,
-    West,
-    East,
-}
-
-impl fmt::Display for Direction {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Direction::North => "north",
-                Direction::South => "south",
-                Direction::West => "west",
-                Direction::East => "east",
-            }
-        )
-    }
-}
-
-impl Into<i64> for Direction {
-    fn into(self) -> i64 {
-        match self {
-            Direction::North => 1,
-            Direction::South => 2,
-            Direction::West => 3,
-            Direction::East => 4,
-        }
-    }
-}
+impl TryFrom<char> for Tile {
+    type Error = Error;
 
-impl From<&Direction> for i64 {
-    fn from(value: &Direction) -> i64 {
+    fn try_from(value: char) -> Result<Self, Self::Error> {
         match value {
-            Direction::North => 1,
-            Direction::South => 2,
-            Direction::West => 3,
-            Direction::East => 4,
+            '.' => Ok(Tile::Floor),
+            '#' => Ok(Tile::Wall),
+            'O' => Ok(Tile::Oxygen),
+            ' ' => Ok(Tile::Unknown),
+            _ => Err(format_err!("Unknown tile \"{}\"", value)),
         }
     }
 }
@@ -166,216 +78,117 @@ impl TryFrom<i64> for MoveResult {
     }
 }
 
-fn point_in_direction(point: &Point, direction: &Direction) -> Point {
-    match direction {
-        Direction::North => Point {
-            x: point.x,
-            y: point.y - 1,
-        },
-        Direction::South => Point {
-            x: point.x,
-            y: point.y + 1,
-        },
-        Direction::West => Point {
-            x: point.x - 1,
-            y: point.y,
-        },
-        Direction::East => Point {
-            x: point.x + 1,
-            y: point.y,
-        },
-    }
-}
-
-fn move_robot(robot: &mut Point, direction: &Direction) {
-    match direction {
-        Direction::North => robot.y -= 1,
-        Direction::South => robot.y += 1,
-        Direction::West => robot.x -= 1,
-        Direction::East => robot.x += 1,
-    }
-}
-
-fn get_path(map: &Map, start: &Point, end: &Point) -> Option<Vec<Direction>> {
-    let directions = vec![
-        Direction::North,
-        Direction::South,
-        Direction::East,
-        Direction::West,
-    ];
-
-    let mut visited = BTreeSet::new();
-
-    let mut to_visit = VecDeque::new();
-    let mut paths = VecDeque::new();
-
-    to_visit.push_back(start.as_tuple());
-    paths.push_back(Vec::new());
-
-    let end_tuple = end.as_tuple();
-
-    while !to_visit.is_empty() {
-        let next = to_visit.pop_front().unwrap();
-        let next_path = paths.pop_front().unwrap();
-
-        if visited.contains(&next) {
-            continue;
-        }
-
-        visited.insert(next);
-
-        if next == end_tuple {
-            return Some(next_path);
-        }
-
-        for direction in directions.iter() {
-            let next_point = point_in_direction(&Point::from_tuple(&next), direction);
-            if let Tile::Unknown | Tile::Wall = map.get_point(&next_point) {
-                continue;
-            }
+// The repair droid is told which way to move and reports whether it hit a
+// wall, moved, or moved onto the oxygen system
+struct Droid;
 
-            let mut further_path = next_path.clone();
-            further_path.push(direction.clone());
+impl Protocol for Droid {
+    type Command = Heading;
+    type Response = MoveResult;
+    type Tile = Tile;
 
-            let point_tuple = next_point.as_tuple();
-            to_visit.push_back(point_tuple);
+    const RESPONSE_LEN: usize = 1;
 
-            paths.push_back(further_path);
-        }
+    fn encode(&self, heading: &Heading, _: &Pose, _: &World<Tile>) -> Vec<i64> {
+        vec![match heading {
+            Heading::North => 1,
+            Heading::South => 2,
+            Heading::West => 3,
+            Heading::East => 4,
+        }]
     }
 
-    None
-}
-
-fn follow_path(program: &mut Program, robot: &mut Point, path: &Vec<Direction>) -> Result<()> {
-    for direction in path.iter() {
-        let mut inputs = VecDeque::new();
-        inputs.push_back(direction.into());
-        let output = program.run_to_next_output(&mut inputs)?.unwrap();
-
-        let move_result: MoveResult = output.try_into()?;
+    fn decode(&self, outputs: &[i64]) -> Result<MoveResult> {
+        outputs[0].try_into()
+    }
 
-        match move_result {
-            MoveResult::HitWall => {
-                return Err(format_err!(
-                    "Error moving requested direction (robot at position {}, direction {:?})",
-                    robot,
-                    direction
-                ));
-            }
+    fn apply(
+        &self,
+        heading: &Heading,
+        result: &MoveResult,
+        pose: &mut Pose,
+        world: &mut World<Tile>,
+    ) -> Result<()> {
+        let next = heading.step(&pose.position);
+        pose.heading = *heading;
+
+        match result {
+            MoveResult::HitWall => world.set(&next, Tile::Wall),
             MoveResult::MovedOneStep => {
-                move_robot(robot, &direction);
+                pose.position = next;
+                world.set(&pose.position, Tile::Floor);
             }
             MoveResult::MovedOneStepAndFoundOxygen => {
-                move_robot(robot, &direction);
+                pose.position = next;
+                world.set(&pose.position, Tile::Oxygen);
             }
         }
-    }
-
-    Ok(())
-}
 
-fn move_once_in_direction(
-    program: &mut Program,
-    robot: &mut Point,
-    map: &mut Map,
-    direction: &Direction,
-) -> Result<MoveResult> {
-    let mut inputs = VecDeque::new();
-    inputs.push_back(direction.into());
-    let output = program.run_to_next_output(&mut inputs)?.unwrap();
-
-    let move_result: MoveResult = output.try_into()?;
-
-    match move_result {
-        MoveResult::HitWall => {
-            // Set this one more in the direction
-            map.set_point(&point_in_direction(&robot, direction), &Tile::Wall);
-        }
-        MoveResult::MovedOneStep => {
-            move_robot(robot, &direction);
-            map.set_point(&robot, &Tile::Floor);
-        }
-        MoveResult::MovedOneStepAndFoundOxygen => {
-            move_robot(robot, &direction);
-            map.set_point(&robot, &Tile::Oxygen);
-        }
+        Ok(())
     }
-
-    Ok(move_result)
 }
 
-fn populate_map(program: &mut Program, map: &mut Map, start: &Point) -> Result<Option<Point>> {
-    let directions = vec![
-        Direction::North,
-        Direction::South,
-        Direction::East,
-        Direction::West,
-    ];
-
-    let mut visited = BTreeSet::new();
-
-    let mut to_visit = VecDeque::new();
-
-    for direction in directions.iter() {
-        to_visit.push_front((start.x, start.y, direction));
-        //to_visit.push_back((start.x, start.y, direction));
-    }
-
-    let mut oxygen_point = None;
-
-    let mut previous_point = start.clone();
-    while !to_visit.is_empty() {
-        let next = to_visit.pop_front().unwrap();
-
-        if visited.contains(&next) {
-            continue;
-        }
-
-        //println!("\x1B[2J{}", map.to_string(&previous_point));
-
-        visited.insert(next);
+struct Exploration {
+    oxygen: Option<Point>,
+    moves: u64,
+}
 
-        let mut point = Point {
-            x: next.0,
-            y: next.1,
+// Maps the whole maze depth first. The droid only ever probes cells it knows
+// nothing about, and when there are none left around it, it steps back the
+// way it came. Once it's back at the start with nothing left to probe, every
+// reachable cell has been seen.
+fn explore(robot: &mut Robot<Droid>) -> Result<Exploration> {
+    let start = robot.pose.position.clone();
+    robot.world.set(&start, Tile::Floor);
+
+    let mut oxygen = None;
+    let mut moves = 0;
+
+    // How the droid got to where it is, so it can retrace its steps
+    let mut path: Vec<Heading> = Vec::new();
+
+    loop {
+        let here = robot.pose.position.clone();
+        let unknown = Heading::ALL
+            .iter()
+            .find(|heading| matches!(robot.world.get(&heading.step(&here)), Tile::Unknown));
+
+        let heading = match (unknown, path.last()) {
+            (Some(heading), _) => *heading,
+            (None, Some(heading)) => heading.reverse(),
+            (None, None) => break,
         };
 
-        // Navigate to the next point
-        let back_path = get_path(&map, &previous_point, &point).ok_or(format_err!(
-            "Unable to find path from {} to {}",
-            previous_point,
-            point
-        ))?;
-        follow_path(program, &mut previous_point, &back_path)?;
-
-        let next_direction = next.2;
-        let result = move_once_in_direction(program, &mut point, map, &next_direction)?;
-        if let MoveResult::MovedOneStep | MoveResult::MovedOneStepAndFoundOxygen = result {
-            previous_point = point.clone();
-
-            if let MoveResult::MovedOneStepAndFoundOxygen = result {
-                oxygen_point = Some(point.clone());
-            }
+        let result = robot
+            .step(&heading)?
+            .ok_or(format_err!("Droid halted while exploring"))?;
+        moves += 1;
 
-            for direction in directions.iter() {
-                to_visit.push_front((point.x, point.y, direction));
-                //to_visit.push_back((point.x, point.y, direction));
+        match (result, unknown) {
+            (MoveResult::HitWall, Some(_)) => {}
+            (MoveResult::HitWall, None) => {
+                return Err(format_err!(
+                    "Hit a wall backing up {:?} from {}",
+                    heading,
+                    here
+                ));
+            }
+            (result, Some(_)) => {
+                if let MoveResult::MovedOneStepAndFoundOxygen = result {
+                    oxygen = Some(robot.pose.position.clone());
+                }
+                path.push(heading);
+            }
+            (_, None) => {
+                path.pop();
             }
         }
     }
 
-    Ok(oxygen_point)
+    Ok(Exploration { oxygen, moves })
 }
 
-fn count_shortest_path(map: &Map, start: &Point, end: &Point) -> Option<u64> {
-    let directions = vec![
-        Direction::North,
-        Direction::South,
-        Direction::East,
-        Direction::West,
-    ];
-
+fn count_shortest_path(map: &World<Tile>, start: &Point, end: &Point) -> Option<u64> {
     let mut scores = BTreeMap::new();
 
     let mut visited = BTreeSet::new();
@@ -396,18 +209,18 @@ fn count_shortest_path(map: &Map, start: &Point, end: &Point) -> Option<u64> {
 
         visited.insert(next);
 
-        let point_score = scores.get(&next).unwrap().clone();
+        let point_score = *scores.get(&next).unwrap();
 
-        for direction in directions.iter() {
-            let next_point = point_in_direction(&Point::from_tuple(&next), direction);
-            if let Tile::Unknown | Tile::Wall = map.get_point(&next_point) {
+        for heading in Heading::ALL.iter() {
+            let next_point = heading.step(&Point::from_tuple(&next));
+            if let Tile::Unknown | Tile::Wall = map.get(&next_point) {
                 continue;
             }
 
             let point_tuple = next_point.as_tuple();
             to_visit.push_back(point_tuple);
 
-            let next_score = scores.get(&point_tuple).unwrap_or(&std::u64::MAX).clone();
+            let next_score = *scores.get(&point_tuple).unwrap_or(&u64::MAX);
             scores.insert(point_tuple, min(next_score, point_score + 1));
         }
     }
@@ -415,14 +228,7 @@ fn count_shortest_path(map: &Map, start: &Point, end: &Point) -> Option<u64> {
     scores.get(&end.as_tuple()).cloned()
 }
 
-fn visit_all(map: &mut Map, start: &Point) -> u64 {
-    let directions = vec![
-        Direction::North,
-        Direction::South,
-        Direction::East,
-        Direction::West,
-    ];
-
+fn visit_all(map: &World<Tile>, start: &Point) -> u64 {
     let mut scores = BTreeMap::new();
 
     let mut visited = BTreeSet::new();
@@ -445,18 +251,18 @@ fn visit_all(map: &mut Map, start: &Point) -> u64 {
 
         visited.insert(next);
 
-        let point_score = scores.get(&next).unwrap().clone();
+        let point_score = *scores.get(&next).unwrap();
 
-        for direction in directions.iter() {
-            let next_point = point_in_direction(&Point::from_tuple(&next), direction);
-            if let Tile::Unknown | Tile::Wall = map.get_point(&next_point) {
+        for heading in Heading::ALL.iter() {
+            let next_point = heading.step(&Point::from_tuple(&next));
+            if let Tile::Unknown | Tile::Wall = map.get(&next_point) {
                 continue;
             }
 
             let point_tuple = next_point.as_tuple();
             to_visit.push_back(point_tuple);
 
-            let mut next_score = scores.get(&point_tuple).unwrap_or(&std::u64::MAX).clone();
+            let mut next_score = *scores.get(&point_tuple).unwrap_or(&u64::MAX);
             next_score = min(next_score, point_score + 1);
             max_count = max(max_count, next_score);
 
@@ -467,26 +273,146 @@ fn visit_all(map: &mut Map, start: &Point) -> u64 {
     max_count
 }
 
+include!("../../common/args.rs");
+
 fn main() -> Result<()> {
     env_logger::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let mut map = Map::new();
-    let robot = Point::zero();
-    map.set_point(&robot, &Tile::Floor);
+    // Runs any ASCII Intcode program interactively instead of the droid
+    if let Some(filename) = flag_value("--ascii") {
+        let mut program = Program::from_file(&filename)?;
+        program.set_limits(Limits::sandbox());
+        return AsciiProgram::new(program).interactive();
+    }
+
+    // Prints the optimized listing of any program instead of the droid
+    if let Some(filename) = flag_value("--optimize") {
+        let tape = Tape::from_file(&filename)?;
+        let optimized = optimize(&tape);
+        print!("{}", optimized);
+        println!(
+            "{} reachable instruction(s) in {} region(s), {} left as is, {} rewritten, \
+             {} of {} cell(s) effectively constant",
+            optimized.lines.len(),
+            optimized.regions.len(),
+            optimized
+                .regions
+                .iter()
+                .filter(|region| region.skipped.is_some())
+                .count(),
+            optimized.rewritten().count(),
+            (0..tape.len())
+                .filter(|a| optimized.is_constant(*a))
+                .count(),
+            tape.len()
+        );
+
+        if let Some(output) = flag_value("--output") {
+            std::fs::write(output, optimized.to_tape()?.to_text())?;
+        }
+
+        return Ok(());
+    }
+
+    // Answers both parts from a map saved by an earlier run
+    if let Some(filename) = flag_value("--map") {
+        let maze = Maze::load(&filename)?;
+        let oxygen_point = maze
+            .oxygen()
+            .ok_or(format_err!("No oxygen system in {}", filename))?;
+        println!("Found oxygen at {}", oxygen_point);
+        println!(
+            "Shortest_path: {}",
+            count_shortest_path(&maze.world, &maze.start, &oxygen_point)
+                .ok_or(format_err!("No path from the start to the oxygen system"))?
+        );
+        println!("Minutes to fill: {}", visit_all(&maze.world, &oxygen_point));
+        return Ok(());
+    }
 
     let mut program = Program::from_file("input.txt")?;
 
-    match populate_map(&mut program, &mut map, &robot)? {
+    if let Some(filename) = flag_value("--replay") {
+        let recording = Recording::load(&filename)?;
+        replay(&mut program, &recording)?;
+        println!(
+            "Replay matched {} recorded event(s)",
+            recording.events.len()
+        );
+        return Ok(());
+    }
+
+    let record_to = flag_value("--record");
+    if record_to.is_some() {
+        program.start_recording();
+    }
+
+    let mut robot = Robot::new(program, Droid, Pose::new(Point::zero(), Heading::North));
+    let exploration = explore(&mut robot)?;
+    debug!("Droid went through {} pose(s)", robot.trajectory().len());
+    println!("Explored the maze in {} move(s)", exploration.moves);
+
+    match exploration.oxygen {
         Some(oxygen_point) => {
             println!("Found oxygen at {}", oxygen_point);
             println!(
                 "Shortest_path: {}",
-                count_shortest_path(&map, &Point::zero(), &oxygen_point).unwrap()
+                count_shortest_path(&robot.world, &Point::zero(), &oxygen_point)
+                    .ok_or(format_err!("No path from the start to the oxygen system"))?
             );
-            println!("Minutes to fill: {}", visit_all(&mut map, &oxygen_point));
+            println!("Minutes to fill: {}", visit_all(&robot.world, &oxygen_point));
         }
         None => println!("Unable to find oxygen"),
     }
 
+    if let Some(filename) = flag_value("--save-map") {
+        Maze {
+            world: robot.world.clone(),
+            start: Point::zero(),
+        }
+        .save(&filename)?;
+    }
+
+    if let Some(filename) = record_to {
+        robot.brain.get_recording().unwrap().save(&filename)?;
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A corridor three cells long with the oxygen system at its east end.
+    // The program keeps the droid's x in cell 63 and walls off everything
+    // but the corridor.
+    const CORRIDOR: &str = "3,64,1008,64,4,65,1005,65,21,1008,64,3,65,1005,65,35,104,0,1105,1,0,\
+                            1007,63,2,65,1006,65,16,1001,63,1,63,1105,1,46,107,0,63,65,1006,65,\
+                            16,1001,63,-1,63,1008,63,2,65,1005,65,58,104,1,1105,1,0,104,2,1105,\
+                            1,0,0,0,0";
+
+    #[test]
+    fn test_explore() -> Result<()> {
+        let brain: Program = CORRIDOR.parse()?;
+        let mut robot = Robot::new(brain, Droid, Pose::new(Point::zero(), Heading::North));
+        let exploration = explore(&mut robot)?;
+
+        // Three probes from each cell, two steps back, then the start's
+        // west side
+        assert_eq!(exploration.moves, 12);
+        assert_eq!(exploration.oxygen, Some(Point { x: 2, y: 0 }));
+
+        // Every floor cell and every wall around them
+        assert_eq!(
+            robot.world.render(|_, tile| char::from(&tile)),
+            " ### \n#..O#\n ### "
+        );
+        assert_eq!(
+            count_shortest_path(&robot.world, &Point::zero(), &Point { x: 2, y: 0 }),
+            Some(2)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{format_err, Context, Error, Result};
+
+use crate::point::Point;
+use crate::robot::World;
+use crate::Tile;
+
+// Marks where the droid started, which is always floor
+const START: char = 'S';
+
+// An explored maze, saved so it can be solved without running the droid again
+pub struct Maze {
+    pub world: World<Tile>,
+    pub start: Point,
+}
+
+impl Maze {
+    pub fn load(filename: &str) -> Result<Self> {
+        std::fs::read_to_string(filename)
+            .with_context(|| format!("Failed to read map {}", filename))?
+            .parse()
+    }
+
+    pub fn save(&self, filename: &str) -> Result<()> {
+        std::fs::write(filename, self.to_string() + "\n")
+            .with_context(|| format!("Failed to write map {}", filename))
+    }
+
+    pub fn oxygen(&self) -> Option<Point> {
+        self.world
+            .iter()
+            .find(|(_, tile)| matches!(tile, Tile::Oxygen))
+            .map(|(point, _)| point.clone())
+    }
+}
+
+// The tile characters, with S for the start and spaces for cells never seen
+impl fmt::Display for Maze {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = self.world.render(|point, tile| {
+            if *point == self.start {
+                START
+            } else {
+                char::from(&tile)
+            }
+        });
+
+        write!(f, "{}", text)
+    }
+}
+
+// Points come back relative to the start, which ends up at (0, 0)
+impl FromStr for Maze {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut tiles = Vec::new();
+        let mut start = None;
+
+        for (y, line) in input.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let point = Point {
+                    x: x as i64,
+                    y: y as i64,
+                };
+                let tile = match c {
+                    START if start.is_some() => {
+                        return Err(format_err!("Second start at line {}", y + 1));
+                    }
+                    START => {
+                        start = Some(point.clone());
+                        Tile::Floor
+                    }
+                    _ => Tile::try_from(c)
+                        .with_context(|| format!("Bad map at line {}, column {}", y + 1, x + 1))?,
+                };
+                tiles.push((point, tile));
+            }
+        }
+
+        let start = start.ok_or(format_err!("Map has no start marked"))?;
+        let mut world = World::new();
+        for (point, tile) in tiles {
+            if let Tile::Unknown = tile {
+                continue;
+            }
+            world.set(
+                &Point {
+                    x: point.x - start.x,
+                    y: point.y - start.y,
+                },
+                tile,
+            );
+        }
+
+        Ok(Self {
+            world,
+            start: Point::zero(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAZE: &str = " ## \n#S.#\n#.O#\n ## ";
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let maze: Maze = MAZE.parse()?;
+
+        assert_eq!(maze.start, Point::zero());
+        assert_eq!(maze.oxygen(), Some(Point { x: 1, y: 1 }));
+        assert!(matches!(maze.world.get(&Point { x: -1, y: 0 }), Tile::Wall));
+        assert!(matches!(maze.world.get(&Point { x: 0, y: 0 }), Tile::Floor));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        assert_eq!(MAZE.parse::<Maze>()?.to_string(), MAZE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("day15_maze_{}.txt", std::process::id()));
+        let filename = path.to_str().unwrap();
+
+        MAZE.parse::<Maze>()?.save(filename)?;
+        let loaded = Maze::load(filename);
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(loaded?.to_string(), MAZE);
+        assert!(Maze::load(filename).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_start() {
+        let error = "#.#".parse::<Maze>().err().unwrap();
+        assert_eq!(error.to_string(), "Map has no start marked");
+    }
+
+    #[test]
+    fn test_second_start() {
+        let error = "S.\n.S".parse::<Maze>().err().unwrap();
+        assert_eq!(error.to_string(), "Second start at line 2");
+    }
+
+    #[test]
+    fn test_bad_tile() {
+        let error = "S?".parse::<Maze>().err().unwrap();
+        assert_eq!(
+            format!("{:#}", error),
+            "Bad map at line 1, column 2: Unknown tile \"?\""
+        );
+    }
+}
@@ -0,0 +1,1016 @@
+// Peephole optimization over a tape. Every instruction keeps its address and
+// length, so rewrites are limited to what fits in the original cells.
+//
+// Reachable code is split into regions, runs of instructions that are only
+// entered at the top. A region is left as it is when the analysis can't
+// reason about it: it uses relative mode operands, the program can write over
+// one of its instructions before it runs, or it ends in a jump whose target
+// isn't fixed.
+//
+// Every write is kept with the instruction making it and the instructions
+// that can run after that one, so it only matters to code it can reach both
+// in memory and in time. Relative mode operands reach the cells the relative
+// base allows at that point. A jump that can't be followed, or an instruction
+// whose opcode can change, may go on to code the analysis never saw, which
+// could read or write any cell.
+//
+// Its only consumer is the --optimize listing in main.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use anyhow::{format_err, Result};
+
+use crate::intcode::{FetchMode, Instruction, OpCode, Tape};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    Position(usize),
+    Immediate(i64),
+    Relative(i64),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Position(address) => write!(f, "[{}]", address),
+            Operand::Immediate(value) => write!(f, "#{}", value),
+            Operand::Relative(offset) => write!(f, "[rb{:+}]", offset),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Op {
+    Add(Operand, Operand, Operand),
+    Multiply(Operand, Operand, Operand),
+    LessThan(Operand, Operand, Operand),
+    Equals(Operand, Operand, Operand),
+    Input(Operand),
+    Output(Operand),
+    JumpIfTrue(Operand, Operand),
+    JumpIfFalse(Operand, Operand),
+    AdjustRelativeBase(Operand),
+    Terminate,
+    // Anything that fails when run, like an unknown opcode
+    Fault,
+    // The rest are only produced by optimizing
+    Set(i64, Operand),
+    Jump(usize),
+    Nop,
+}
+
+impl Op {
+    fn destination(&self) -> Option<Operand> {
+        match self {
+            Op::Add(_, _, d)
+            | Op::Multiply(_, _, d)
+            | Op::LessThan(_, _, d)
+            | Op::Equals(_, _, d)
+            | Op::Input(d)
+            | Op::Set(_, d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    fn relative(&self) -> bool {
+        self.reads()
+            .into_iter()
+            .chain(self.destination())
+            .any(|operand| matches!(operand, Operand::Relative(_)))
+    }
+
+    fn reads(&self) -> Vec<Operand> {
+        match self {
+            Op::Add(a, b, _)
+            | Op::Multiply(a, b, _)
+            | Op::LessThan(a, b, _)
+            | Op::Equals(a, b, _)
+            | Op::JumpIfTrue(a, b)
+            | Op::JumpIfFalse(a, b) => vec![*a, *b],
+            Op::Output(a) | Op::AdjustRelativeBase(a) => vec![*a],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Add(a, b, d) => write!(f, "ADD {} {} -> {}", a, b, d),
+            Op::Multiply(a, b, d) => write!(f, "MUL {} {} -> {}", a, b, d),
+            Op::LessThan(a, b, d) => write!(f, "LT {} {} -> {}", a, b, d),
+            Op::Equals(a, b, d) => write!(f, "EQ {} {} -> {}", a, b, d),
+            Op::Input(d) => write!(f, "IN -> {}", d),
+            Op::Output(a) => write!(f, "OUT {}", a),
+            Op::JumpIfTrue(a, b) => write!(f, "JT {} {}", a, b),
+            Op::JumpIfFalse(a, b) => write!(f, "JF {} {}", a, b),
+            Op::AdjustRelativeBase(a) => write!(f, "ARB {}", a),
+            Op::Terminate => write!(f, "HALT"),
+            Op::Fault => write!(f, "FAULT"),
+            Op::Set(value, d) => write!(f, "SET {} -> {}", value, d),
+            Op::Jump(target) => write!(f, "JMP {}", target),
+            Op::Nop => write!(f, "NOP"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Line {
+    pub len: usize,
+    pub op: Op,
+    pub original: Op,
+}
+
+impl Line {
+    fn cells(&self, address: usize) -> std::ops::Range<usize> {
+        address..address + self.len
+    }
+}
+
+// Cells from `first` to `last`, where no `last` means every cell from `first`
+// on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Span {
+    pub first: usize,
+    pub last: Option<usize>,
+}
+
+impl Span {
+    const EVERYWHERE: Span = Span {
+        first: 0,
+        last: None,
+    };
+
+    fn cell(address: usize) -> Self {
+        Span {
+            first: address,
+            last: Some(address),
+        }
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        address >= self.first && self.last.is_none_or(|last| address <= last)
+    }
+}
+
+// What one instruction may write
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Writer {
+    pub cells: BTreeSet<Span>,
+    // Instructions that can run after this one has
+    pub later: BTreeSet<usize>,
+}
+
+// Every write the program may make, by the address of the instruction
+// making it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Writes {
+    pub by: BTreeMap<usize, Writer>,
+}
+
+impl Writes {
+    fn contains(&self, address: usize) -> bool {
+        self.by
+            .values()
+            .any(|writer| writer.cells.iter().any(|span| span.contains(address)))
+    }
+
+    // Whether `cell` can change before the instruction at `address` runs,
+    // any of the times it does
+    fn before(&self, address: usize, cell: usize) -> bool {
+        self.by.values().any(|writer| {
+            writer.later.contains(&address) && writer.cells.iter().any(|span| span.contains(cell))
+        })
+    }
+}
+
+// Bounds on the relative base, where None is no bound on that side
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Base {
+    low: Option<i64>,
+    high: Option<i64>,
+}
+
+impl Base {
+    const UNKNOWN: Base = Base {
+        low: None,
+        high: None,
+    };
+
+    fn join(self, other: Base) -> Base {
+        let low = |a: Option<i64>, b: Option<i64>| Some(a?.min(b?));
+        let high = |a: Option<i64>, b: Option<i64>| Some(a?.max(b?));
+        Base {
+            low: low(self.low, other.low),
+            high: high(self.high, other.high),
+        }
+    }
+
+    // Overflowing fails in the VM, so losing the bound is safe
+    fn add(self, delta: i64) -> Base {
+        Base {
+            low: self.low.and_then(|low| low.checked_add(delta)),
+            high: self.high.and_then(|high| high.checked_add(delta)),
+        }
+    }
+
+    // Cells a relative operand can reach. None if every one of them is
+    // negative, which fails.
+    fn span(self, offset: i64) -> Option<Span> {
+        let last = match self.high.and_then(|high| high.checked_add(offset)) {
+            Some(last) if last < 0 => return None,
+            Some(last) => Some(last as usize),
+            None => None,
+        };
+        let first = self
+            .low
+            .and_then(|low| low.checked_add(offset))
+            .map_or(0, |first| first.max(0) as usize);
+
+        Some(Span { first, last })
+    }
+}
+
+// Cells an operand reads or writes
+fn reach(base: Base, operand: Operand) -> Option<Span> {
+    match operand {
+        Operand::Position(address) => Some(Span::cell(address)),
+        Operand::Immediate(_) => None,
+        Operand::Relative(offset) => base.span(offset),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub start: usize,
+    // One past the last cell of the last instruction
+    pub end: usize,
+    pub addresses: Vec<usize>,
+    // Why the region is left as it is, if it is
+    pub skipped: Option<String>,
+}
+
+pub struct Optimized {
+    tape: Tape,
+    // Every instruction the program can reach, by address
+    pub lines: BTreeMap<usize, Line>,
+    pub regions: Vec<Region>,
+    // Every cell the program can write to. Everything else keeps its
+    // starting value for the whole run.
+    pub writes: Writes,
+}
+
+fn operand(mode: &FetchMode, value: i64) -> Option<Operand> {
+    match mode {
+        FetchMode::Immediate => Some(Operand::Immediate(value)),
+        FetchMode::Position if value < 0 => None,
+        FetchMode::Position => Some(Operand::Position(value as usize)),
+        FetchMode::Relative => Some(Operand::Relative(value)),
+    }
+}
+
+// Decodes the instruction at `address` the same way the VM would. Returns
+// its length and operation.
+fn decode(tape: &Tape, address: usize) -> (usize, Op) {
+    let instruction = match Instruction::new(tape, address) {
+        Ok(instruction) => instruction,
+        Err(_) => return (1, Op::Fault),
+    };
+    let len = instruction.arguments.len() + 1;
+
+    let mut operands = Vec::new();
+    for argument in instruction.arguments.iter() {
+        match operand(&argument.mode, argument.value) {
+            Some(operand) => operands.push(operand),
+            // A negative address fails as soon as it's used
+            None => return (len, Op::Fault),
+        }
+    }
+
+    // Immediate destinations are written as if they were positions
+    let destination = |index: usize| match operands[index] {
+        Operand::Immediate(value) if value >= 0 => Some(Operand::Position(value as usize)),
+        Operand::Immediate(_) => None,
+        operand => Some(operand),
+    };
+    let binary = |make: fn(Operand, Operand, Operand) -> Op| match destination(2) {
+        Some(d) => make(operands[0], operands[1], d),
+        None => Op::Fault,
+    };
+
+    let op = match instruction.opcode {
+        OpCode::Add => binary(Op::Add),
+        OpCode::Multiply => binary(Op::Multiply),
+        OpCode::LessThan => binary(Op::LessThan),
+        OpCode::Equals => binary(Op::Equals),
+        OpCode::Input => destination(0).map_or(Op::Fault, Op::Input),
+        OpCode::Output => Op::Output(operands[0]),
+        OpCode::JumpIfTrue => Op::JumpIfTrue(operands[0], operands[1]),
+        OpCode::JumpIfFalse => Op::JumpIfFalse(operands[0], operands[1]),
+        OpCode::AdjustRelativeBase => Op::AdjustRelativeBase(operands[0]),
+        OpCode::Terminate => Op::Terminate,
+    };
+
+    (len, op)
+}
+
+// Value of operand `index` of the instruction at `address`, if it's the same
+// every time the instruction runs
+fn fixed_value(
+    tape: &Tape,
+    writes: &Writes,
+    address: usize,
+    index: usize,
+    operand: Operand,
+) -> Option<i64> {
+    if writes.before(address, address + 1 + index) {
+        return None;
+    }
+
+    match operand {
+        Operand::Immediate(value) => Some(value),
+        Operand::Position(cell) if !writes.before(address, cell) => tape.get(cell),
+        _ => None,
+    }
+}
+
+struct Flow {
+    lines: BTreeMap<usize, Line>,
+    // Where jumps with a fixed target land
+    targets: BTreeSet<usize>,
+    // Instructions that may go on to code the analysis never saw: jumps it
+    // can't follow, and instructions whose opcode can change
+    escapes: BTreeSet<usize>,
+    // Where each instruction can go next, leaving out escapes
+    next: BTreeMap<usize, Vec<usize>>,
+}
+
+// Finds every instruction reachable from the start, assuming only the cells
+// in `writes` ever change
+fn explore(tape: &Tape, writes: &Writes) -> Flow {
+    let mut flow = Flow {
+        lines: BTreeMap::new(),
+        targets: BTreeSet::new(),
+        escapes: BTreeSet::new(),
+        next: BTreeMap::new(),
+    };
+    let mut pending = vec![0];
+
+    while let Some(address) = pending.pop() {
+        if flow.lines.contains_key(&address) {
+            continue;
+        }
+
+        let (len, op) = decode(tape, address);
+        let fixed =
+            |index: usize, operand: Operand| fixed_value(tape, writes, address, index, operand);
+        let (jumps, falls_through) = match &op {
+            Op::Terminate | Op::Fault => (None, false),
+            Op::JumpIfTrue(condition, target) | Op::JumpIfFalse(condition, target) => {
+                let jump_if = matches!(op, Op::JumpIfTrue(..));
+                match fixed(0, *condition) {
+                    Some(condition) if (condition != 0) == jump_if => (Some(*target), false),
+                    Some(_) => (None, true),
+                    None => (Some(*target), true),
+                }
+            }
+            _ => (None, true),
+        };
+
+        let mut next = Vec::new();
+        if let Some(target) = jumps {
+            match fixed(1, target) {
+                Some(target) if target >= 0 => {
+                    flow.targets.insert(target as usize);
+                    next.push(target as usize);
+                }
+                // Taking the jump fails
+                Some(_) => {}
+                None => {
+                    flow.escapes.insert(address);
+                }
+            }
+        }
+        if falls_through {
+            next.push(address + len);
+        }
+        // Whatever it turns into could go anywhere
+        if writes.before(address, address) {
+            flow.escapes.insert(address);
+        }
+
+        pending.extend(next.iter().cloned());
+        flow.next.insert(address, next);
+        flow.lines.insert(
+            address,
+            Line {
+                len,
+                original: op.clone(),
+                op,
+            },
+        );
+    }
+
+    flow
+}
+
+// Where each instruction can go next. Escapes can go to any of them.
+fn successors<'a>(flow: &'a Flow, everything: &'a [usize], address: usize) -> &'a [usize] {
+    if flow.escapes.contains(&address) {
+        everything
+    } else {
+        &flow.next[&address]
+    }
+}
+
+// The instructions that can run after each one has
+fn ordering(flow: &Flow) -> BTreeMap<usize, BTreeSet<usize>> {
+    let everything: Vec<usize> = flow.lines.keys().cloned().collect();
+
+    everything
+        .iter()
+        .map(|start| {
+            let mut later = BTreeSet::new();
+            let mut pending = successors(flow, &everything, *start).to_vec();
+            while let Some(address) = pending.pop() {
+                if later.insert(address) {
+                    pending.extend(successors(flow, &everything, address));
+                }
+            }
+            (*start, later)
+        })
+        .collect()
+}
+
+// The relative base as each instruction starts
+fn relative_bases(tape: &Tape, flow: &Flow, writes: &Writes) -> BTreeMap<usize, Base> {
+    let everything: Vec<usize> = flow.lines.keys().cloned().collect();
+    let mut bases: BTreeMap<usize, Base> = BTreeMap::new();
+    let mut pending = vec![(
+        0,
+        Base {
+            low: Some(0),
+            high: Some(0),
+        },
+    )];
+
+    while let Some((address, base)) = pending.pop() {
+        let base = match bases.get(&address) {
+            None => base,
+            Some(old) if old.join(base) == *old => continue,
+            // A bound that has moved once could keep moving around a loop,
+            // so it's dropped instead
+            Some(old) => {
+                let joined = old.join(base);
+                Base {
+                    low: joined.low.filter(|_| joined.low == old.low),
+                    high: joined.high.filter(|_| joined.high == old.high),
+                }
+            }
+        };
+        bases.insert(address, base);
+
+        let after = match flow.lines[&address].op {
+            Op::AdjustRelativeBase(delta) => match fixed_value(tape, writes, address, 0, delta) {
+                Some(delta) => base.add(delta),
+                None => Base::UNKNOWN,
+            },
+            _ => base,
+        };
+        if flow.escapes.contains(&address) {
+            pending.extend(everything.iter().map(|next| (*next, Base::UNKNOWN)));
+        }
+        pending.extend(flow.next[&address].iter().map(|next| (*next, after)));
+    }
+
+    bases
+}
+
+// Every write the code in `flow` can make, on top of `writes`
+fn find_writes(tape: &Tape, flow: &Flow, writes: &Writes) -> Writes {
+    let later = ordering(flow);
+    let bases = relative_bases(tape, flow, writes);
+
+    let mut found = writes.clone();
+    for (address, line) in flow.lines.iter() {
+        let mut cells = BTreeSet::new();
+        if let Some(destination) = line.op.destination() {
+            // The destination comes after everything read
+            let index = line.op.reads().len();
+            if writes.before(*address, address + 1 + index) {
+                cells.insert(Span::EVERYWHERE);
+            } else {
+                cells.extend(reach(bases[address], destination));
+            }
+        }
+        if flow.escapes.contains(address) {
+            cells.insert(Span::EVERYWHERE);
+        }
+
+        if !cells.is_empty() {
+            let writer = found.by.entry(*address).or_default();
+            writer.cells.extend(cells);
+            writer.later.extend(later[address].iter().cloned());
+        }
+    }
+
+    found
+}
+
+fn skip_reason(flow: &Flow, writes: &Writes, address: usize, line: &Line) -> Option<String> {
+    if line.original.relative() {
+        Some(format!("instruction at {} uses relative mode", address))
+    } else if line.cells(address).any(|cell| writes.before(address, cell)) {
+        Some(format!(
+            "instruction at {} is overwritten by the program",
+            address
+        ))
+    } else if flow.escapes.contains(&address) {
+        Some(format!("jump at {} doesn't have a fixed target", address))
+    } else {
+        None
+    }
+}
+
+// Starts a new region wherever control can arrive other than from the
+// instruction just before
+fn split(flow: &Flow, writes: &Writes) -> Vec<Region> {
+    let mut regions: Vec<Region> = Vec::new();
+    let mut previous: Option<(usize, &Line)> = None;
+
+    for (address, line) in flow.lines.iter() {
+        let continues = match previous {
+            Some((previous_address, previous)) => {
+                previous_address + previous.len == *address
+                    && !flow.targets.contains(address)
+                    && !matches!(
+                        previous.original,
+                        Op::JumpIfTrue(..) | Op::JumpIfFalse(..) | Op::Terminate | Op::Fault
+                    )
+            }
+            None => false,
+        };
+        if !continues {
+            regions.push(Region {
+                start: *address,
+                end: *address,
+                addresses: Vec::new(),
+                skipped: None,
+            });
+        }
+
+        let region = regions.last_mut().unwrap();
+        region.end = address + line.len;
+        region.addresses.push(*address);
+        if region.skipped.is_none() {
+            region.skipped = skip_reason(flow, writes, *address, line);
+        }
+
+        previous = Some((*address, line));
+    }
+
+    regions
+}
+
+// Rewrites the instruction at `address` using what's known about its operands
+fn simplify(tape: &Tape, writes: &Writes, address: usize, op: &Op) -> Op {
+    let fixed =
+        |index: usize, operand: Operand| match fixed_value(tape, writes, address, index, operand) {
+            Some(value) => Operand::Immediate(value),
+            None => operand,
+        };
+
+    match op.clone() {
+        Op::Add(a, b, d) | Op::Multiply(a, b, d) | Op::LessThan(a, b, d) | Op::Equals(a, b, d) => {
+            let (a, b) = (fixed(0, a), fixed(1, b));
+            let folded = match (op, a, b) {
+                (Op::Add(..), Operand::Immediate(x), Operand::Immediate(y)) => x.checked_add(y),
+                (Op::Multiply(..), Operand::Immediate(x), Operand::Immediate(y)) => {
+                    x.checked_mul(y)
+                }
+                (Op::LessThan(..), Operand::Immediate(x), Operand::Immediate(y)) => {
+                    Some((x < y) as i64)
+                }
+                (Op::Equals(..), Operand::Immediate(x), Operand::Immediate(y)) => {
+                    Some((x == y) as i64)
+                }
+                _ => None,
+            };
+            if let Some(value) = folded {
+                return Op::Set(value, d);
+            }
+
+            // x + 0 and x * 1 written back to x
+            let identity = match op {
+                Op::Add(..) => Some(0),
+                Op::Multiply(..) => Some(1),
+                _ => None,
+            };
+            let unchanged =
+                |x: Operand, y: Operand| x == d && Some(y) == identity.map(Operand::Immediate);
+            if unchanged(a, b) || unchanged(b, a) {
+                return Op::Nop;
+            }
+
+            match op {
+                Op::Add(..) => Op::Add(a, b, d),
+                Op::Multiply(..) => Op::Multiply(a, b, d),
+                Op::LessThan(..) => Op::LessThan(a, b, d),
+                _ => Op::Equals(a, b, d),
+            }
+        }
+        Op::Output(a) => Op::Output(fixed(0, a)),
+        Op::JumpIfTrue(a, b) | Op::JumpIfFalse(a, b) => {
+            let jump_if = matches!(op, Op::JumpIfTrue(..));
+            match (fixed(0, a), fixed(1, b)) {
+                (Operand::Immediate(condition), Operand::Immediate(target)) if target >= 0 => {
+                    if (condition != 0) == jump_if {
+                        Op::Jump(target as usize)
+                    } else {
+                        Op::Nop
+                    }
+                }
+                (a, b) if jump_if => Op::JumpIfTrue(a, b),
+                (a, b) => Op::JumpIfFalse(a, b),
+            }
+        }
+        op => op,
+    }
+}
+
+pub fn optimize(tape: &Tape) -> Optimized {
+    // Start by assuming nothing is written and grow the set until the
+    // reachable code agrees with it
+    let mut writes = Writes::default();
+    let mut flow = explore(tape, &writes);
+    loop {
+        let found = find_writes(tape, &flow, &writes);
+        if found == writes {
+            break;
+        }
+        writes = found;
+        flow = explore(tape, &writes);
+    }
+
+    // How many times each cell is decoded as part of an instruction, and
+    // the cells that may be read as data
+    let bases = relative_bases(tape, &flow, &writes);
+    let mut decoded: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut reads: BTreeSet<Span> = BTreeSet::new();
+    for (address, line) in flow.lines.iter() {
+        for cell in line.cells(*address) {
+            *decoded.entry(cell).or_insert(0) += 1;
+        }
+        for (index, operand) in line.op.reads().into_iter().enumerate() {
+            // An immediate only changes value, but an address that changes
+            // could point anywhere
+            let immediate = matches!(operand, Operand::Immediate(_));
+            if writes.before(*address, address + 1 + index) && !immediate {
+                reads.insert(Span::EVERYWHERE);
+            } else {
+                reads.extend(reach(bases[address], operand));
+            }
+        }
+        if flow.escapes.contains(address) {
+            reads.insert(Span::EVERYWHERE);
+        }
+    }
+    let exclusive = |cell: usize| {
+        decoded.get(&cell) == Some(&1) && !reads.iter().any(|span| span.contains(cell))
+    };
+
+    let regions = split(&flow, &writes);
+    let mut lines = flow.lines;
+    for region in regions.iter().filter(|region| region.skipped.is_none()) {
+        for address in region.addresses.iter() {
+            let line = lines.get_mut(address).unwrap();
+            let op = simplify(tape, &writes, *address, &line.op);
+            if op == line.op {
+                continue;
+            }
+
+            // Only the cells the rewrite changes have to be left alone by
+            // everything else
+            let cells = match encode(&op, *address, line.len) {
+                Ok(cells) => cells,
+                Err(_) => continue,
+            };
+            let safe = cells
+                .into_iter()
+                .enumerate()
+                .filter(|(i, value)| tape.get(address + i) != Some(*value))
+                .all(|(i, _)| exclusive(address + i));
+            if safe {
+                line.op = op;
+            }
+        }
+    }
+
+    Optimized {
+        tape: tape.clone(),
+        lines,
+        regions,
+        writes,
+    }
+}
+
+fn encode(op: &Op, address: usize, len: usize) -> Result<Vec<i64>> {
+    let mut cells = vec![0];
+    let mut code = 0;
+    let mut push = |operand: Operand, cells: &mut Vec<i64>| {
+        let (mode, value) = match operand {
+            Operand::Position(address) => (0, address as i64),
+            Operand::Immediate(value) => (1, value),
+            Operand::Relative(offset) => (2, offset),
+        };
+        code += mode * 10i64.pow(cells.len() as u32 + 1);
+        cells.push(value);
+    };
+
+    let opcode = match op {
+        Op::Add(a, b, d) | Op::Multiply(a, b, d) | Op::LessThan(a, b, d) | Op::Equals(a, b, d) => {
+            push(*a, &mut cells);
+            push(*b, &mut cells);
+            push(*d, &mut cells);
+            match op {
+                Op::Add(..) => 1,
+                Op::Multiply(..) => 2,
+                Op::LessThan(..) => 7,
+                _ => 8,
+            }
+        }
+        Op::Input(d) => {
+            push(*d, &mut cells);
+            3
+        }
+        Op::Output(a) => {
+            push(*a, &mut cells);
+            4
+        }
+        Op::JumpIfTrue(a, b) | Op::JumpIfFalse(a, b) => {
+            push(*a, &mut cells);
+            push(*b, &mut cells);
+            if let Op::JumpIfTrue(..) = op {
+                5
+            } else {
+                6
+            }
+        }
+        Op::AdjustRelativeBase(a) => {
+            push(*a, &mut cells);
+            9
+        }
+        Op::Set(value, d) => {
+            push(Operand::Immediate(*value), &mut cells);
+            push(Operand::Immediate(0), &mut cells);
+            push(*d, &mut cells);
+            1
+        }
+        Op::Jump(target) => {
+            push(Operand::Immediate(1), &mut cells);
+            push(Operand::Immediate(*target as i64), &mut cells);
+            5
+        }
+        // Jumps to the next instruction. Only ever replaces instructions at
+        // least three cells long.
+        Op::Nop => {
+            push(Operand::Immediate(1), &mut cells);
+            push(Operand::Immediate((address + len) as i64), &mut cells);
+            5
+        }
+        Op::Terminate => 99,
+        // Whatever cells make up a fault, they aren't a valid instruction
+        Op::Fault => {
+            return Err(format_err!(
+                "Instruction at {} faults and has no encoding",
+                address
+            ))
+        }
+    };
+    cells[0] = code + opcode;
+
+    Ok(cells)
+}
+
+impl Optimized {
+    pub fn is_constant(&self, address: usize) -> bool {
+        !self.writes.contains(address)
+    }
+
+    pub fn rewritten(&self) -> impl Iterator<Item = (&usize, &Line)> {
+        self.lines
+            .iter()
+            .filter(|(_, line)| line.op != line.original)
+    }
+
+    // The original tape with every rewritten instruction re-encoded in place
+    pub fn to_tape(&self) -> Result<Tape> {
+        let mut values = self.tape.values();
+        for (address, line) in self.rewritten() {
+            if values.len() < address + line.len {
+                values.resize(address + line.len, 0);
+            }
+            for (i, value) in encode(&line.op, *address, line.len)?
+                .into_iter()
+                .enumerate()
+            {
+                values[address + i] = value;
+            }
+        }
+
+        Ok(Tape::new(&values))
+    }
+}
+
+impl fmt::Display for Optimized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for region in self.regions.iter() {
+            match &region.skipped {
+                Some(reason) => writeln!(
+                    f,
+                    "; {}..{} left as is: {}",
+                    region.start, region.end, reason
+                )?,
+                None => writeln!(f, "; {}..{}", region.start, region.end)?,
+            }
+
+            for address in region.addresses.iter() {
+                let line = &self.lines[address];
+                if line.op == line.original {
+                    writeln!(f, "{:>6}: {}", address, line.op)?;
+                } else {
+                    writeln!(f, "{:>6}: {:<28} ; was {}", address, line.op, line.original)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::intcode::Program;
+
+    fn outputs(tape: &Tape) -> Result<VecDeque<i64>> {
+        Program::new(tape).run(&mut VecDeque::new())
+    }
+
+    #[test]
+    fn test_folds_immediates() -> Result<()> {
+        let tape: Tape = "1101,2,3,13,1102,4,5,14,4,13,4,14,99,0,0".parse()?;
+        let optimized = optimize(&tape);
+
+        assert_eq!(optimized.lines[&0].op, Op::Set(5, Operand::Position(13)));
+        assert_eq!(optimized.lines[&4].op, Op::Set(20, Operand::Position(14)));
+        assert_eq!(optimized.rewritten().count(), 2);
+        assert_eq!(
+            optimized.to_tape()?.values()[..8],
+            [1101, 5, 0, 13, 1101, 20, 0, 14]
+        );
+
+        assert_eq!(outputs(&optimized.to_tape()?)?, VecDeque::from(vec![5, 20]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jump_if_true_becomes_jump() -> Result<()> {
+        // The output of 1 at 3 is never reached. The second jump reads its
+        // condition and target from cells nothing writes.
+        let tape: Tape = "1105,1,6,104,1,99,5,15,16,104,1,99,104,2,99,1,12".parse()?;
+        let optimized = optimize(&tape);
+
+        assert_eq!(optimized.lines[&0].op, Op::Jump(6));
+        assert_eq!(optimized.lines[&6].op, Op::Jump(12));
+        assert!(!optimized.lines.contains_key(&3));
+        assert!(optimized.is_constant(15) && optimized.is_constant(16));
+
+        assert_eq!(outputs(&optimized.to_tape()?)?, VecDeque::from(vec![2]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_drops_no_op() -> Result<()> {
+        // Adds 0 to, then multiplies by 1, a cell that holds an input
+        let tape: Tape = "3,13,1001,13,0,13,102,1,13,13,4,13,99,0".parse()?;
+        let optimized = optimize(&tape);
+
+        assert_eq!(optimized.lines[&2].op, Op::Nop);
+        assert_eq!(optimized.lines[&6].op, Op::Nop);
+        assert!(!optimized.is_constant(13));
+
+        let mut program = Program::new(&optimized.to_tape()?);
+        assert_eq!(
+            program.run(&mut VecDeque::from(vec![42]))?,
+            VecDeque::from(vec![42])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_skips_relative_region() -> Result<()> {
+        // 0..7 adds two constants then jumps over a gap, 9..20 writes through
+        // relative mode and outputs the sum
+        let tape: Tape =
+            "1101,2,3,20,1105,1,9,99,99,21101,4,5,30,1101,6,7,21,4,20,99,0,0".parse()?;
+        let optimized = optimize(&tape);
+
+        let regions: Vec<(usize, usize, Option<String>)> = optimized
+            .regions
+            .iter()
+            .map(|region| (region.start, region.end, region.skipped.clone()))
+            .collect();
+        assert_eq!(
+            regions,
+            vec![
+                (0, 7, None),
+                (
+                    9,
+                    20,
+                    Some("instruction at 9 uses relative mode".to_string())
+                )
+            ]
+        );
+
+        assert_eq!(optimized.lines[&0].op, Op::Set(5, Operand::Position(20)));
+        assert_eq!(optimized.lines[&4].op, Op::Jump(9));
+        assert_eq!(optimized.rewritten().count(), 2);
+        // The relative base never moves, so the relative write only reaches 30
+        assert_eq!(
+            optimized.writes.by[&9].cells,
+            vec![Span::cell(30)].into_iter().collect()
+        );
+
+        assert_eq!(outputs(&optimized.to_tape()?)?, outputs(&tape)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_write_into_code() -> Result<()> {
+        // The relative mode write at 0 turns the ADD at 7 into 9 + 3, so
+        // folding it as 2 + 3 would output 5
+        let tape: Tape = "21101,9,0,8,1105,1,7,1101,2,3,20,4,20,99,0,0,0,0,0,0,0".parse()?;
+        let optimized = optimize(&tape);
+
+        assert_eq!(
+            optimized.regions[1].skipped,
+            Some("instruction at 7 is overwritten by the program".to_string())
+        );
+        assert_eq!(optimized.lines[&7].op, optimized.lines[&7].original);
+        assert_eq!(outputs(&tape)?, VecDeque::from(vec![12]));
+        assert_eq!(outputs(&optimized.to_tape()?)?, VecDeque::from(vec![12]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_skips_overwritten_region() -> Result<()> {
+        // The first instruction writes 2 into the instruction at 7, which
+        // then outputs 2 instead of 0
+        let tape: Tape = "1101,1,1,8,1105,1,7,1101,0,0,20,4,20,99,0,0,0,0,0,0,0".parse()?;
+        let optimized = optimize(&tape);
+
+        assert_eq!(
+            optimized.regions[1].skipped,
+            Some("instruction at 7 is overwritten by the program".to_string())
+        );
+        assert_eq!(optimized.lines[&0].op, Op::Set(2, Operand::Position(8)));
+        assert_eq!(optimized.lines[&7].op, optimized.lines[&7].original);
+        assert!(!optimized.is_constant(8) && !optimized.is_constant(20));
+
+        assert_eq!(outputs(&optimized.to_tape()?)?, VecDeque::from(vec![2]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrites_code_written_after_it_runs() -> Result<()> {
+        // The ADD writes over its own destination, but never runs again
+        let tape: Tape = "1101,2,3,3,4,3,99".parse()?;
+        let optimized = optimize(&tape);
+
+        assert_eq!(optimized.regions[0].skipped, None);
+        assert_eq!(optimized.lines[&0].op, Op::Set(5, Operand::Position(3)));
+        // Only the cells that change have to be unread, and 3 doesn't
+        assert_eq!(optimized.to_tape()?.values(), vec![1101, 5, 0, 3, 4, 3, 99]);
+
+        assert_eq!(outputs(&optimized.to_tape()?)?, VecDeque::from(vec![5]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fault_has_no_encoding() {
+        assert_eq!(
+            encode(&Op::Fault, 12, 1).unwrap_err().to_string(),
+            "Instruction at 12 faults and has no encoding"
+        );
+    }
+}
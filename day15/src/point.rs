@@ -26,20 +26,6 @@ impl Point {
         }
     }
 
-    pub fn min() -> Self {
-        Self {
-            x: std::i64::MIN,
-            y: std::i64::MIN,
-        }
-    }
-
-    pub fn max() -> Self {
-        Self {
-            x: std::i64::MAX,
-            y: std::i64::MAX,
-        }
-    }
-
     pub fn from_tuple(tuple: &(i64, i64)) -> Self {
         Self {x: tuple.0, y: tuple.1}
     }
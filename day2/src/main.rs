@@ -1,107 +1,119 @@
-use std::convert::{TryInto, TryFrom};
+mod intcode;
+mod search;
+mod symbolic;
 
-use anyhow::{format_err, Error, Result};
+use std::collections::VecDeque;
+use std::str::FromStr;
 
-enum OpCode {
-    Add,
-    Multiply,
-    Terminate,
-}
-
-impl TryFrom<i64> for OpCode {
-    type Error = Error;
-
-    fn try_from(value: i64) -> Result<Self, Self::Error> {
-        match value {
-            1 => Ok(OpCode::Add),
-            2 => Ok(OpCode::Multiply),
-            99 => Ok(OpCode::Terminate),
-            _ => Err(format_err!("Unknown opcode {}", value)),
-        }
-    }
-}
-
-enum InstructionResult {
-    Continue,
-    Terminate,
-}
-
-fn string_to_vec(input: &str) -> Result<Vec<i64>> {
-    let mut ret = Vec::new();
-    for num in input.trim().split(",") {
-        ret.push(num.parse()?);
-    }
-    Ok(ret)
-}
-
-fn read_input(filename: &str) -> Result<Vec<i64>> {
-    let data = std::fs::read_to_string(filename)?;
-
-    string_to_vec(&data)
-}
-
-fn get_position(program: &Vec<i64>, position: usize) -> i64 {
-    *program.get(position).unwrap()
-}
-
-fn get_value(program: &Vec<i64>, position: usize) -> i64 {
-    *program.get(get_position(program, position) as usize).unwrap()
-}
+use anyhow::Result;
 
-fn run_instruction(program: &mut Vec<i64>, pc: usize) -> Result<InstructionResult> {
-    match program.get(pc) {
-        Some(opcode) => match (*opcode).try_into()? {
-            OpCode::Add => {
-                let result_offset = get_position(program, pc + 3) as usize;
-                program[result_offset] =
-                    get_value(program, pc + 1) + get_value(program, pc + 2);
+use crate::intcode::{Program, Tape};
+use crate::search::{parse_range, Patch, Search};
+use crate::symbolic::{Goal, SymbolicExecutor};
 
-                Ok(InstructionResult::Continue)
-            },
-            OpCode::Multiply => {
-                let result_offset = get_position(program, pc + 3) as usize;
-                program[result_offset] =
-                    get_value(program, pc + 1) * get_value(program, pc + 2);
+include!("../../common/args.rs");
 
-                Ok(InstructionResult::Continue)
-            },
-            OpCode::Terminate => {
-                Ok(InstructionResult::Terminate)
-            },
-        },
-        None => Err(format_err!("Program counter is out of bounds")),
+fn main() -> Result<()> {
+    let tape = Tape::from_str(&std::fs::read_to_string("input.txt")?)?;
+
+    // The "1202 program alarm" state
+    let mut program = Program::new(&tape);
+    program.set_memory_value(1, 12)?;
+    program.set_memory_value(2, 2)?;
+    program.run(&mut VecDeque::new())?;
+    println!("Value at position 0: {}", program.get_memory_value(0));
+
+    let target = match flag_value("--target") {
+        Some(target) => target.parse()?,
+        None => 19690720,
+    };
+    let address = match flag_value("--address") {
+        Some(address) => address.parse()?,
+        None => 0,
+    };
+    let mut patches = flag_values("--patch")
+        .iter()
+        .map(|patch| patch.parse())
+        .collect::<Result<Vec<Patch>>>()?;
+    if patches.is_empty() {
+        patches = vec!["1=0..99".parse()?, "2=0..99".parse()?];
     }
-}
-
-fn run_program(program: &Vec<i64>, input_a: i64, input_b: i64) -> Result<i64> {
-    let mut program = program.clone();
 
-    program[1] = input_a;
-    program[2] = input_b;
-
-    let mut pc = 0;
-
-    loop {
-        match run_instruction(&mut program, pc)? {
-            InstructionResult::Continue => pc += 4,
-            InstructionResult::Terminate => break,
+    let inputs = flag_values("--input")
+        .iter()
+        .map(|range| parse_range(range))
+        .collect::<Result<Vec<_>>>()?;
+    let output = match flag_value("--output") {
+        Some(index) => Some(index.parse()?),
+        None => None,
+    };
+
+    // The brute-force search runs without input and only checks memory, so
+    // symbolic inputs and output goals are left to the solver
+    if inputs.is_empty() && output.is_none() {
+        let search = Search::new(&tape, patches.clone(), address, target);
+        let found = if std::env::args().any(|arg| arg == "--all") {
+            search.all()?
+        } else {
+            search.first()?.into_iter().collect()
+        };
+
+        if found.is_empty() {
+            println!("Nothing leaves {} at position {}", target, address);
+        }
+        for values in found {
+            let shown: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+            match values.as_slice() {
+                [noun, verb] => println!(
+                    "Found! ({}), value is {}",
+                    shown.join(", "),
+                    100 * noun + verb
+                ),
+                _ => println!("Found! ({})", shown.join(", ")),
+            }
         }
     }
 
-    Ok(program[0])
-}
-
-fn main() -> Result<()> {
-    let program = read_input("input.txt")?;
+    let mut executor = SymbolicExecutor::new(&tape);
+    for patch in patches.iter() {
+        executor.symbolic_memory(
+            patch.address,
+            &format!("[{}]", patch.address),
+            *patch.values.start(),
+            *patch.values.end(),
+        );
+    }
+    for (i, range) in inputs.iter().enumerate() {
+        executor.symbolic_input(&format!("input {}", i), *range.start(), *range.end());
+    }
 
-    for i in 0..100 {
-        for j in 0..100 {
-            if run_program(&program, i, j)? == 19690720 {
-                println!("Found! ({}, {}), value is {}", i, j, 100 * i + j);
+    let goal = match output {
+        Some(index) => Goal::Output {
+            index,
+            value: target,
+        },
+        None => Goal::Memory {
+            address,
+            value: target,
+        },
+    };
+    match executor.solve(&goal)? {
+        Some(solution) => {
+            let values: Vec<i64> = patches
+                .iter()
+                .filter_map(|patch| solution.value(&format!("[{}]", patch.address)))
+                .collect();
+            match values.as_slice() {
+                [noun, verb] => println!(
+                    "Solved symbolically! ({}), value is {}",
+                    solution,
+                    100 * noun + verb
+                ),
+                _ => println!("Solved symbolically! ({})", solution),
             }
         }
+        None => println!("No symbolic solution found"),
     }
 
     Ok(())
 }
-
@@ -0,0 +1,294 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use anyhow::{format_err, Context, Error, Result};
+use log::debug;
+
+use crate::intcode::{Program, Tape};
+
+// Patching can turn a program into an infinite loop, so each candidate gets
+// this many instructions to halt
+const MAX_INSTRUCTIONS: u64 = 1_000_000;
+
+// A memory cell to patch before running, and the values to try in it
+#[derive(Clone, Debug)]
+pub struct Patch {
+    pub address: usize,
+    pub values: RangeInclusive<i64>,
+}
+
+impl Patch {
+    // None when the range is too big to count
+    fn len(&self) -> Option<u64> {
+        let len = *self.values.end() as i128 - *self.values.start() as i128 + 1;
+        u64::try_from(len).ok()
+    }
+}
+
+// Parses "ADDRESS=VALUE" or "ADDRESS=MIN..MAX"
+impl FromStr for Patch {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = input.split('=').collect();
+        if parts.len() != 2 {
+            return Err(format_err!("Patch \"{}\" should look like ADDRESS=MIN..MAX", input));
+        }
+
+        let address = parts[0]
+            .trim()
+            .parse()
+            .with_context(|| format!("Bad address in patch \"{}\"", input))?;
+        let values =
+            parse_range(parts[1]).with_context(|| format!("Bad range in patch \"{}\"", input))?;
+
+        Ok(Patch { address, values })
+    }
+}
+
+// Parses "VALUE" or "MIN..MAX"
+pub fn parse_range(input: &str) -> Result<RangeInclusive<i64>> {
+    let bounds: Vec<&str> = input.split("..").collect();
+    let (min, max) = match bounds.as_slice() {
+        [value] => (value.trim().parse()?, value.trim().parse()?),
+        [min, max] => (min.trim().parse()?, max.trim().parse()?),
+        _ => return Err(format_err!("Range \"{}\" should look like MIN..MAX", input)),
+    };
+
+    if min > max {
+        return Err(format_err!("Empty range \"{}\"", input));
+    }
+
+    Ok(min..=max)
+}
+
+// Finds values for the patched cells that leave `target` in `address` once
+// the program halts. Every combination is tried, in order, across all cores.
+pub struct Search {
+    tape: Tape,
+    patches: Vec<Patch>,
+    address: usize,
+    target: i64,
+}
+
+impl Search {
+    pub fn new(tape: &Tape, patches: Vec<Patch>, address: usize, target: i64) -> Self {
+        Self {
+            tape: tape.clone(),
+            patches,
+            address,
+            target,
+        }
+    }
+
+    fn candidate_count(&self) -> Result<u64> {
+        self.patches
+            .iter()
+            .try_fold(1u64, |count, patch| count.checked_mul(patch.len()?))
+            .ok_or(format_err!("Too many combinations to search"))
+    }
+
+    // The first patch changes slowest, so candidates come out in the same
+    // order nested loops would produce them
+    fn candidate(&self, mut index: u64) -> Vec<i64> {
+        let mut values = vec![0; self.patches.len()];
+        for (i, patch) in self.patches.iter().enumerate().rev() {
+            // Only called once candidate_count has checked every length
+            let len = patch.len().unwrap_or(u64::MAX);
+            values[i] = (*patch.values.start() as i128 + (index % len) as i128) as i64;
+            index /= len;
+        }
+
+        values
+    }
+
+    // A candidate that makes the program fail just isn't a match
+    fn matches(&self, values: &[i64]) -> bool {
+        let mut program = Program::new(&self.tape);
+        let result = self
+            .patches
+            .iter()
+            .zip(values.iter())
+            .try_for_each(|(patch, value)| program.set_memory_value(patch.address, *value))
+            .and_then(|_| program.run_with_limit(&mut VecDeque::new(), MAX_INSTRUCTIONS));
+
+        match result {
+            Ok(_) => program.get_memory_value(self.address) == self.target,
+            Err(e) => {
+                debug!("{:?} failed: {:#}", values, e);
+                false
+            }
+        }
+    }
+
+    // Hands every match to `keep`. With `first_only`, workers stop once
+    // nothing left to try could come before a match already found.
+    fn evaluate(&self, first_only: bool, keep: &(dyn Fn(u64, Vec<i64>) + Sync)) -> Result<()> {
+        let count = self.candidate_count()?;
+        let next = AtomicU64::new(0);
+        let earliest = AtomicU64::new(u64::MAX);
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= count || (first_only && index > earliest.load(Ordering::Relaxed))
+                        {
+                            return;
+                        }
+
+                        let values = self.candidate(index);
+                        if self.matches(&values) {
+                            earliest.fetch_min(index, Ordering::Relaxed);
+                            keep(index, values);
+                        }
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| {
+                    worker
+                        .join()
+                        .map_err(|_| format_err!("Search thread panicked"))
+                })
+                .collect::<Result<Vec<()>>>()
+        })?;
+
+        Ok(())
+    }
+
+    // The match nested loops would find first
+    pub fn first(&self) -> Result<Option<Vec<i64>>> {
+        let best: Mutex<Option<(u64, Vec<i64>)>> = Mutex::new(None);
+
+        self.evaluate(true, &|index, values| {
+            let mut best = best.lock().unwrap();
+            let better = match best.as_ref() {
+                Some((best_index, _)) => index < *best_index,
+                None => true,
+            };
+            if better {
+                *best = Some((index, values));
+            }
+        })?;
+
+        Ok(best.into_inner().unwrap().map(|(_, values)| values))
+    }
+
+    // Every match, in order
+    pub fn all(&self) -> Result<Vec<Vec<i64>>> {
+        let found = Mutex::new(Vec::new());
+
+        self.evaluate(false, &|index, values| {
+            found.lock().unwrap().push((index, values));
+        })?;
+
+        let mut found = found.into_inner().unwrap();
+        found.sort_by_key(|(index, _)| *index);
+
+        Ok(found.into_iter().map(|(_, values)| values).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(input: &str) -> Result<(usize, i64, i64)> {
+        let patch: Patch = input.parse()?;
+        Ok((patch.address, *patch.values.start(), *patch.values.end()))
+    }
+
+    #[test]
+    fn test_patch_parsing() -> Result<()> {
+        assert_eq!(patch("1=0..99")?, (1, 0, 99));
+        assert_eq!(patch(" 2 = -3 .. 3 ")?, (2, -3, 3));
+        assert_eq!(patch("7=42")?, (7, 42, 42));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_patches() {
+        let error = |input: &str| patch(input).unwrap_err().to_string();
+
+        assert_eq!(error("1"), "Patch \"1\" should look like ADDRESS=MIN..MAX");
+        assert_eq!(error("x=1"), "Bad address in patch \"x=1\"");
+        assert_eq!(error("1=1..2..3"), "Bad range in patch \"1=1..2..3\"");
+        assert_eq!(error("1=a..3"), "Bad range in patch \"1=a..3\"");
+        assert_eq!(error("1=5..3"), "Bad range in patch \"1=5..3\"");
+    }
+
+    #[test]
+    fn test_parse_range() -> Result<()> {
+        assert_eq!(parse_range("0..99")?, 0..=99);
+        assert_eq!(parse_range(" -3 .. 3 ")?, -3..=3);
+        assert_eq!(parse_range("42")?, 42..=42);
+
+        let error = |input: &str| parse_range(input).unwrap_err().to_string();
+        assert_eq!(
+            error("1..2..3"),
+            "Range \"1..2..3\" should look like MIN..MAX"
+        );
+        assert_eq!(error("5..3"), "Empty range \"5..3\"");
+        assert!(parse_range("a..3").is_err());
+
+        Ok(())
+    }
+
+    // Leaves the sum of cells 1 and 2 in cell 0
+    fn sum_search(max_b: i64) -> Result<Search> {
+        let tape: Tape = "1101,0,0,0,99".parse()?;
+        let patches = vec!["1=0..5".parse()?, format!("2=0..{}", max_b).parse()?];
+        Ok(Search::new(&tape, patches, 0, 5))
+    }
+
+    #[test]
+    fn test_all_in_order() -> Result<()> {
+        assert_eq!(
+            sum_search(5)?.all()?,
+            vec![
+                vec![0, 5],
+                vec![1, 4],
+                vec![2, 3],
+                vec![3, 2],
+                vec![4, 1],
+                vec![5, 0]
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_stops_early() -> Result<()> {
+        // Far too many candidates to try them all, but the first match is
+        // the sixth one
+        assert_eq!(sum_search(1 << 50)?.first()?, Some(vec![0, 5]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_input() -> Result<()> {
+        let tape: Tape = include_str!("../input.txt").parse()?;
+        // Narrowed around the answer, since every candidate runs the whole
+        // program
+        let patches = vec!["1=70..80".parse()?, "2=60..80".parse()?];
+        let search = Search::new(&tape, patches, 0, 19690720);
+
+        assert_eq!(search.first()?, Some(vec![78, 70]));
+        assert_eq!(search.all()?, vec![vec![78, 70]]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1049 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::{format_err, Context, Result};
+use log::{debug, trace};
+
+use crate::intcode::{FetchMode, Instruction, OpCode, Tape};
+
+pub type Symbol = usize;
+
+// sum(coefficient * symbol) + constant
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinearExpr {
+    terms: BTreeMap<Symbol, i64>,
+    constant: i64,
+}
+
+impl LinearExpr {
+    pub fn constant(value: i64) -> Self {
+        Self {
+            terms: BTreeMap::new(),
+            constant: value,
+        }
+    }
+
+    pub fn symbol(symbol: Symbol) -> Self {
+        let mut terms = BTreeMap::new();
+        terms.insert(symbol, 1);
+
+        Self { terms, constant: 0 }
+    }
+
+    fn as_constant(&self) -> Option<i64> {
+        if self.terms.is_empty() {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    fn add(&self, other: &Self) -> Option<Self> {
+        let mut terms = self.terms.clone();
+        for (symbol, coefficient) in other.terms.iter() {
+            let entry = terms.entry(*symbol).or_insert(0);
+            *entry = entry.checked_add(*coefficient)?;
+            if *entry == 0 {
+                terms.remove(symbol);
+            }
+        }
+
+        Some(Self {
+            terms,
+            constant: self.constant.checked_add(other.constant)?,
+        })
+    }
+
+    fn scale(&self, factor: i64) -> Option<Self> {
+        if factor == 0 {
+            return Some(Self::constant(0));
+        }
+
+        let mut terms = BTreeMap::new();
+        for (symbol, coefficient) in self.terms.iter() {
+            terms.insert(*symbol, coefficient.checked_mul(factor)?);
+        }
+
+        Some(Self {
+            terms,
+            constant: self.constant.checked_mul(factor)?,
+        })
+    }
+
+    fn sub(&self, other: &Self) -> Option<Self> {
+        self.add(&other.scale(-1)?)
+    }
+
+    fn evaluate(&self, assignment: &[i64]) -> Option<i64> {
+        let mut total = self.constant;
+        for (symbol, coefficient) in self.terms.iter() {
+            total = total.checked_add(coefficient.checked_mul(*assignment.get(*symbol)?)?)?;
+        }
+
+        Some(total)
+    }
+}
+
+impl fmt::Display for LinearExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        for (symbol, coefficient) in self.terms.iter() {
+            if *coefficient == 1 {
+                parts.push(format!("s{}", symbol));
+            } else {
+                parts.push(format!("{}*s{}", coefficient, symbol));
+            }
+        }
+        if self.constant != 0 || parts.is_empty() {
+            parts.push(self.constant.to_string());
+        }
+
+        write!(f, "{}", parts.join(" + "))
+    }
+}
+
+// Every constraint is normalized to "expr <relation> 0"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Relation {
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterOrEqual,
+}
+
+impl Relation {
+    fn negate(self) -> Self {
+        match self {
+            Relation::Equal => Relation::NotEqual,
+            Relation::NotEqual => Relation::Equal,
+            Relation::LessThan => Relation::GreaterOrEqual,
+            Relation::GreaterOrEqual => Relation::LessThan,
+        }
+    }
+
+    fn holds(self, value: i64) -> bool {
+        match self {
+            Relation::Equal => value == 0,
+            Relation::NotEqual => value != 0,
+            Relation::LessThan => value < 0,
+            Relation::GreaterOrEqual => value >= 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Constraint {
+    expr: LinearExpr,
+    relation: Relation,
+}
+
+impl Constraint {
+    fn new(expr: LinearExpr, relation: Relation) -> Self {
+        Self { expr, relation }
+    }
+
+    fn holds(&self, assignment: &[i64]) -> bool {
+        match self.expr.evaluate(assignment) {
+            Some(value) => self.relation.holds(value),
+            None => false,
+        }
+    }
+
+    // Constraints without symbols can be decided while exploring
+    fn is_trivially_false(&self) -> bool {
+        match self.expr.as_constant() {
+            Some(value) => !self.relation.holds(value),
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relation = match self.relation {
+            Relation::Equal => "==",
+            Relation::NotEqual => "!=",
+            Relation::LessThan => "<",
+            Relation::GreaterOrEqual => ">=",
+        };
+        write!(f, "{} {} 0", self.expr, relation)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Linear(LinearExpr),
+    // 1 if "expr <relation> 0" holds, 0 otherwise
+    Compare(Constraint),
+    // Nonlinear, overflowed or read through a symbolic address
+    Unknown,
+}
+
+impl Value {
+    fn constant(value: i64) -> Self {
+        Value::Linear(LinearExpr::constant(value))
+    }
+
+    fn as_constant(&self) -> Option<i64> {
+        match self {
+            Value::Linear(expr) => expr.as_constant(),
+            _ => None,
+        }
+    }
+}
+
+impl From<Option<LinearExpr>> for Value {
+    fn from(expr: Option<LinearExpr>) -> Self {
+        match expr {
+            Some(expr) => Value::Linear(expr),
+            None => Value::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct SymbolInfo {
+    name: String,
+    min: i64,
+    max: i64,
+}
+
+pub enum Goal {
+    Memory { address: usize, value: i64 },
+    Output { index: usize, value: i64 },
+}
+
+#[derive(Clone)]
+struct State {
+    memory: BTreeMap<usize, Value>,
+    pc: usize,
+    relative_base: i64,
+    inputs_consumed: usize,
+    outputs: Vec<Value>,
+    constraints: Vec<Constraint>,
+    steps: usize,
+}
+
+impl State {
+    fn get(&self, address: usize) -> Value {
+        self.memory
+            .get(&address)
+            .cloned()
+            .unwrap_or_else(|| Value::constant(0))
+    }
+
+    fn address(&self, value: &Value) -> Option<usize> {
+        let address = value.as_constant()?;
+        if address < 0 {
+            None
+        } else {
+            Some(address as usize)
+        }
+    }
+
+    fn with_constraint(&self, constraint: Constraint) -> Option<Self> {
+        if constraint.is_trivially_false() {
+            return None;
+        }
+
+        let mut state = self.clone();
+        state.constraints.push(constraint);
+        Some(state)
+    }
+}
+
+enum StepResult {
+    Continue,
+    Fork(Vec<State>),
+    Terminated,
+    Abort(String),
+}
+
+#[derive(Debug)]
+pub struct Solution {
+    names: Vec<String>,
+    values: Vec<i64>,
+}
+
+impl Solution {
+    pub fn value(&self, name: &str) -> Option<i64> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .map(|i| self.values[i])
+    }
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .names
+            .iter()
+            .zip(self.values.iter())
+            .map(|(name, value)| format!("{} = {}", name, value))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+pub struct SymbolicExecutor {
+    memory: BTreeMap<usize, Value>,
+    symbols: Vec<SymbolInfo>,
+    inputs: Vec<Symbol>,
+    max_steps: usize,
+    max_paths: usize,
+    max_search: u64,
+}
+
+impl SymbolicExecutor {
+    pub fn new(tape: &Tape) -> Self {
+        let mut memory = BTreeMap::new();
+        for (address, value) in tape.iter() {
+            memory.insert(*address, Value::constant(*value));
+        }
+
+        Self {
+            memory,
+            symbols: Vec::new(),
+            inputs: Vec::new(),
+            max_steps: 100_000,
+            max_paths: 1024,
+            max_search: 10_000_000,
+        }
+    }
+
+    fn add_symbol(&mut self, name: &str, min: i64, max: i64) -> Symbol {
+        self.symbols.push(SymbolInfo {
+            name: name.to_string(),
+            min,
+            max,
+        });
+        self.symbols.len() - 1
+    }
+
+    pub fn symbolic_memory(&mut self, address: usize, name: &str, min: i64, max: i64) -> Symbol {
+        let symbol = self.add_symbol(name, min, max);
+        self.memory
+            .insert(address, Value::Linear(LinearExpr::symbol(symbol)));
+        symbol
+    }
+
+    // Each call stands for the next value the program reads
+    pub fn symbolic_input(&mut self, name: &str, min: i64, max: i64) -> Symbol {
+        let symbol = self.add_symbol(name, min, max);
+        self.inputs.push(symbol);
+        symbol
+    }
+
+    pub fn solve(&self, goal: &Goal) -> Result<Option<Solution>> {
+        Ok(self.solve_all(goal, 1)?.pop())
+    }
+
+    // At most `limit` solutions, so a limit of 0 finds none
+    pub fn solve_all(&self, goal: &Goal, limit: usize) -> Result<Vec<Solution>> {
+        let mut solutions = Vec::new();
+
+        for state in self.explore()? {
+            if solutions.len() >= limit {
+                break;
+            }
+
+            let mut constraints = state.constraints.clone();
+
+            let value = match goal {
+                Goal::Memory { address, value } => (state.get(*address), *value),
+                Goal::Output { index, value } => match state.outputs.get(*index) {
+                    Some(output) => (output.clone(), *value),
+                    None => continue,
+                },
+            };
+
+            match value {
+                (Value::Linear(expr), target) => match expr.sub(&LinearExpr::constant(target)) {
+                    Some(expr) => constraints.push(Constraint::new(expr, Relation::Equal)),
+                    None => continue,
+                },
+                (Value::Compare(constraint), 1) => constraints.push(constraint),
+                (Value::Compare(constraint), 0) => constraints.push(Constraint::new(
+                    constraint.expr,
+                    constraint.relation.negate(),
+                )),
+                (Value::Compare(_), _) => continue,
+                (Value::Unknown, _) => {
+                    debug!("Goal value is unknown on a path, skipping");
+                    continue;
+                }
+            }
+
+            let wanted = limit.saturating_sub(solutions.len());
+            for assignment in self.solve_constraints(&constraints, wanted)? {
+                solutions.push(Solution {
+                    names: self.symbols.iter().map(|s| s.name.clone()).collect(),
+                    values: assignment,
+                });
+            }
+        }
+
+        Ok(solutions)
+    }
+
+    // Runs every feasible path to termination, returning the final states
+    fn explore(&self) -> Result<Vec<State>> {
+        let mut finished = Vec::new();
+        let mut to_visit = vec![State {
+            memory: self.memory.clone(),
+            pc: 0,
+            relative_base: 0,
+            inputs_consumed: 0,
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            steps: 0,
+        }];
+        let mut path_count = 1;
+
+        while let Some(mut state) = to_visit.pop() {
+            loop {
+                if state.steps >= self.max_steps {
+                    debug!("Path exceeded {} steps, dropping it", self.max_steps);
+                    break;
+                }
+
+                match self.step(&mut state)? {
+                    StepResult::Continue => {}
+                    StepResult::Fork(states) => {
+                        path_count += states.len() - 1;
+                        if path_count > self.max_paths {
+                            return Err(format_err!(
+                                "Exceeded maximum of {} paths",
+                                self.max_paths
+                            ));
+                        }
+                        to_visit.extend(states);
+                        break;
+                    }
+                    StepResult::Terminated => {
+                        finished.push(state);
+                        break;
+                    }
+                    StepResult::Abort(reason) => {
+                        debug!("Abandoning path at offset {}: {}", state.pc, reason);
+                        break;
+                    }
+                }
+            }
+        }
+
+        debug!(
+            "Explored {} path(s), {} terminated",
+            path_count,
+            finished.len()
+        );
+
+        Ok(finished)
+    }
+
+    fn decode(&self, state: &State) -> Result<Option<Instruction>> {
+        let code = match state.get(state.pc).as_constant() {
+            Some(code) => code,
+            None => return Ok(None),
+        };
+
+        // Operands may be symbolic, so only the opcode and modes come from
+        // the decoder; operand values are read from symbolic memory instead.
+        let tape = Tape::new(&[code, 0, 0, 0]);
+        let instruction = Instruction::new(&tape, 0)
+            .with_context(|| format!("Failed to decode instruction at offset {}", state.pc))?;
+
+        Ok(Some(instruction))
+    }
+
+    fn operand(&self, state: &State, index: usize) -> Value {
+        state.get(state.pc + index + 1)
+    }
+
+    // Returns the cell an argument reads from, or None for immediates
+    fn source_address(
+        &self,
+        state: &State,
+        instruction: &Instruction,
+        index: usize,
+    ) -> Option<Option<usize>> {
+        let operand = self.operand(state, index);
+        match instruction.arguments[index].mode {
+            FetchMode::Immediate => Some(None),
+            FetchMode::Position => state.address(&operand).map(Some),
+            FetchMode::Relative => {
+                let offset = operand.as_constant()?;
+                state
+                    .address(&Value::constant(offset.checked_add(state.relative_base)?))
+                    .map(Some)
+            }
+        }
+    }
+
+    fn read(&self, state: &State, instruction: &Instruction, index: usize) -> Value {
+        match self.source_address(state, instruction, index) {
+            Some(Some(address)) => state.get(address),
+            Some(None) => self.operand(state, index),
+            None => Value::Unknown,
+        }
+    }
+
+    fn write_address(
+        &self,
+        state: &State,
+        instruction: &Instruction,
+        index: usize,
+    ) -> Option<usize> {
+        let operand = self.operand(state, index);
+        match instruction.arguments[index].mode {
+            FetchMode::Relative => {
+                let offset = operand.as_constant()?;
+                state.address(&Value::constant(offset.checked_add(state.relative_base)?))
+            }
+            _ => state.address(&operand),
+        }
+    }
+
+    // A comparison result consumed as a number is split into its 0 and 1 cases
+    fn fork_on_compare(&self, state: &State, instruction: &Instruction) -> Option<Vec<State>> {
+        let mut addresses = Vec::new();
+        for index in 0..instruction.arguments.len() {
+            addresses.push(state.pc + index + 1);
+            if let Some(Some(address)) = self.source_address(state, instruction, index) {
+                addresses.push(address);
+            }
+        }
+
+        for address in addresses {
+            if let Value::Compare(constraint) = state.get(address) {
+                let mut states = Vec::new();
+                let cases = [
+                    (1, constraint.clone()),
+                    (
+                        0,
+                        Constraint::new(constraint.expr.clone(), constraint.relation.negate()),
+                    ),
+                ];
+                for (value, constraint) in cases {
+                    if let Some(mut forked) = state.with_constraint(constraint) {
+                        forked.memory.insert(address, Value::constant(value));
+                        states.push(forked);
+                    }
+                }
+                return Some(states);
+            }
+        }
+
+        None
+    }
+
+    fn step(&self, state: &mut State) -> Result<StepResult> {
+        let instruction = match self.decode(state)? {
+            Some(instruction) => instruction,
+            None => return Ok(StepResult::Abort("symbolic opcode".to_string())),
+        };
+
+        if let Some(states) = self.fork_on_compare(state, &instruction) {
+            return Ok(StepResult::Fork(states));
+        }
+
+        trace!("[SYM] {} {:?}", state.pc, instruction.opcode);
+
+        state.steps += 1;
+        let next_offset = state.pc + instruction.arguments.len() + 1;
+
+        match instruction.opcode {
+            OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => {
+                let arg1 = self.read(state, &instruction, 0);
+                let arg2 = self.read(state, &instruction, 1);
+                let address = match self.write_address(state, &instruction, 2) {
+                    Some(address) => address,
+                    None => return Ok(StepResult::Abort("symbolic write address".to_string())),
+                };
+
+                let result = match (&instruction.opcode, arg1, arg2) {
+                    (OpCode::Add, Value::Linear(a), Value::Linear(b)) => a.add(&b).into(),
+                    (OpCode::Multiply, Value::Linear(a), Value::Linear(b)) => {
+                        match (a.as_constant(), b.as_constant()) {
+                            (Some(factor), _) => b.scale(factor).into(),
+                            (_, Some(factor)) => a.scale(factor).into(),
+                            _ => Value::Unknown,
+                        }
+                    }
+                    (OpCode::LessThan, Value::Linear(a), Value::Linear(b)) => {
+                        compare(a.sub(&b), Relation::LessThan)
+                    }
+                    (OpCode::Equals, Value::Linear(a), Value::Linear(b)) => {
+                        compare(a.sub(&b), Relation::Equal)
+                    }
+                    _ => Value::Unknown,
+                };
+
+                state.memory.insert(address, result);
+                state.pc = next_offset;
+            }
+            OpCode::Input => {
+                let value = match self.inputs.get(state.inputs_consumed) {
+                    Some(symbol) => Value::Linear(LinearExpr::symbol(*symbol)),
+                    None => return Ok(StepResult::Abort("no input values left".to_string())),
+                };
+                let address = match self.write_address(state, &instruction, 0) {
+                    Some(address) => address,
+                    None => return Ok(StepResult::Abort("symbolic write address".to_string())),
+                };
+
+                state.inputs_consumed += 1;
+                state.memory.insert(address, value);
+                state.pc = next_offset;
+            }
+            OpCode::Output => {
+                let value = self.read(state, &instruction, 0);
+                state.outputs.push(value);
+                state.pc = next_offset;
+            }
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let condition = self.read(state, &instruction, 0);
+                let target = match self.read(state, &instruction, 1).as_constant() {
+                    Some(target) if target >= 0 => target as usize,
+                    _ => return Ok(StepResult::Abort("symbolic jump target".to_string())),
+                };
+                let jump_if_zero = matches!(instruction.opcode, OpCode::JumpIfFalse);
+
+                match condition {
+                    Value::Linear(expr) => match expr.as_constant() {
+                        Some(value) => {
+                            state.pc = if (value == 0) == jump_if_zero {
+                                target
+                            } else {
+                                next_offset
+                            };
+                        }
+                        None => {
+                            let mut states = Vec::new();
+                            let cases = [
+                                (
+                                    Relation::Equal,
+                                    if jump_if_zero { target } else { next_offset },
+                                ),
+                                (
+                                    Relation::NotEqual,
+                                    if jump_if_zero { next_offset } else { target },
+                                ),
+                            ];
+                            for (relation, pc) in cases {
+                                if let Some(mut forked) =
+                                    state.with_constraint(Constraint::new(expr.clone(), relation))
+                                {
+                                    forked.pc = pc;
+                                    states.push(forked);
+                                }
+                            }
+                            return Ok(StepResult::Fork(states));
+                        }
+                    },
+                    _ => return Ok(StepResult::Abort("unknown jump condition".to_string())),
+                }
+            }
+            OpCode::AdjustRelativeBase => {
+                let adjustment = match self.read(state, &instruction, 0).as_constant() {
+                    Some(adjustment) => adjustment,
+                    None => return Ok(StepResult::Abort("symbolic relative base".to_string())),
+                };
+                state.relative_base = match state.relative_base.checked_add(adjustment) {
+                    Some(relative_base) => relative_base,
+                    None => return Ok(StepResult::Abort("relative base overflow".to_string())),
+                };
+                state.pc = next_offset;
+            }
+            OpCode::Terminate => return Ok(StepResult::Terminated),
+        }
+
+        Ok(StepResult::Continue)
+    }
+
+    // Gaussian elimination over the equalities, then a bounded search over
+    // whatever symbols are left free, checking every other constraint.
+    fn solve_constraints(&self, constraints: &[Constraint], limit: usize) -> Result<Vec<Vec<i64>>> {
+        let symbol_count = self.symbols.len();
+
+        let mut rows: Vec<Vec<Fraction>> = Vec::new();
+        for constraint in constraints.iter() {
+            if constraint.relation != Relation::Equal {
+                continue;
+            }
+            let mut row = vec![Fraction::from(0); symbol_count + 1];
+            for (symbol, coefficient) in constraint.expr.terms.iter() {
+                row[*symbol] = Fraction::from(*coefficient);
+            }
+            row[symbol_count] = Fraction::from(constraint.expr.constant);
+            rows.push(row);
+        }
+
+        let mut pivots = Vec::new();
+        let mut pivot_row = 0;
+        for column in 0..symbol_count {
+            let found = (pivot_row..rows.len()).find(|r| !rows[*r][column].is_zero());
+            let found = match found {
+                Some(found) => found,
+                None => continue,
+            };
+            rows.swap(pivot_row, found);
+
+            let divisor = rows[pivot_row][column];
+            for value in rows[pivot_row].iter_mut() {
+                *value = value.div(divisor)?;
+            }
+
+            let pivot_values = rows[pivot_row].clone();
+            for (r, row) in rows.iter_mut().enumerate() {
+                if r == pivot_row || row[column].is_zero() {
+                    continue;
+                }
+                let factor = row[column];
+                for (value, pivot_value) in row.iter_mut().zip(pivot_values.iter()) {
+                    *value = value.sub(factor.mul(*pivot_value)?)?;
+                }
+            }
+
+            pivots.push((column, pivot_row));
+            pivot_row += 1;
+        }
+
+        // 0 == constant rows
+        for row in rows.iter().skip(pivot_row) {
+            if !row[symbol_count].is_zero() {
+                trace!("Inconsistent equality system");
+                return Ok(Vec::new());
+            }
+        }
+
+        let mut mentioned = vec![false; symbol_count];
+        for constraint in constraints.iter() {
+            for symbol in constraint.expr.terms.keys() {
+                mentioned[*symbol] = true;
+            }
+        }
+
+        // Unconstrained symbols are pinned to their minimum
+        let free: Vec<Symbol> = (0..symbol_count)
+            .filter(|s| mentioned[*s] && !pivots.iter().any(|(column, _)| column == s))
+            .collect();
+
+        let mut search_size: u64 = 1;
+        for symbol in free.iter() {
+            let info = &self.symbols[*symbol];
+            // A range too wide to count is over the limit anyway
+            let size = info
+                .max
+                .checked_sub(info.min)
+                .and_then(|span| span.checked_add(1))
+                .map_or(u64::MAX, |size| size.max(0) as u64);
+            search_size = search_size.saturating_mul(size);
+        }
+        if search_size > self.max_search {
+            return Err(format_err!(
+                "Search space of {} assignments exceeds limit of {}",
+                search_size,
+                self.max_search
+            ));
+        }
+
+        let mut solutions = Vec::new();
+        let mut assignment: Vec<i64> = self.symbols.iter().map(|s| s.min).collect();
+
+        'search: loop {
+            if self.complete_assignment(&mut assignment, &pivots, &rows)?
+                && constraints.iter().all(|c| c.holds(&assignment))
+            {
+                solutions.push(assignment.clone());
+                if solutions.len() >= limit {
+                    break;
+                }
+            }
+
+            // Odometer-style increment over the free symbols
+            for symbol in free.iter() {
+                if assignment[*symbol] < self.symbols[*symbol].max {
+                    assignment[*symbol] += 1;
+                    continue 'search;
+                }
+                assignment[*symbol] = self.symbols[*symbol].min;
+            }
+
+            break;
+        }
+
+        Ok(solutions)
+    }
+
+    // Fills in pivot symbols from the free ones, false if any is out of range
+    // or not an integer
+    fn complete_assignment(
+        &self,
+        assignment: &mut [i64],
+        pivots: &[(Symbol, usize)],
+        rows: &[Vec<Fraction>],
+    ) -> Result<bool> {
+        let symbol_count = self.symbols.len();
+
+        for (symbol, row) in pivots.iter() {
+            let mut value = rows[*row][symbol_count].neg();
+            for other in 0..symbol_count {
+                if other == *symbol || rows[*row][other].is_zero() {
+                    continue;
+                }
+                let term = rows[*row][other].mul(Fraction::from(assignment[other]))?;
+                value = value.sub(term)?;
+            }
+
+            let info = &self.symbols[*symbol];
+            match value.as_integer() {
+                Some(value) if value >= info.min && value <= info.max => {
+                    assignment[*symbol] = value;
+                }
+                _ => return Ok(false),
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+fn compare(expr: Option<LinearExpr>, relation: Relation) -> Value {
+    match expr {
+        Some(expr) => match expr.as_constant() {
+            Some(value) => Value::constant(if relation.holds(value) { 1 } else { 0 }),
+            None => Value::Compare(Constraint::new(expr, relation)),
+        },
+        None => Value::Unknown,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Fraction {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl From<i64> for Fraction {
+    fn from(value: i64) -> Self {
+        Self {
+            numerator: value as i128,
+            denominator: 1,
+        }
+    }
+}
+
+impl Fraction {
+    fn new(numerator: i128, denominator: i128) -> Result<Self> {
+        if denominator == 0 {
+            return Err(format_err!("Division by zero in linear solver"));
+        }
+
+        let divisor = gcd(numerator.abs(), denominator.abs()).max(1);
+        let sign = if denominator < 0 { -1 } else { 1 };
+
+        Ok(Self {
+            numerator: sign * numerator / divisor,
+            denominator: sign * denominator / divisor,
+        })
+    }
+
+    fn is_zero(&self) -> bool {
+        self.numerator == 0
+    }
+
+    fn neg(self) -> Self {
+        Self {
+            numerator: -self.numerator,
+            denominator: self.denominator,
+        }
+    }
+
+    fn mul(self, other: Self) -> Result<Self> {
+        Self::new(
+            self.numerator
+                .checked_mul(other.numerator)
+                .ok_or_else(|| format_err!("Overflow in linear solver"))?,
+            self.denominator
+                .checked_mul(other.denominator)
+                .ok_or_else(|| format_err!("Overflow in linear solver"))?,
+        )
+    }
+
+    fn div(self, other: Self) -> Result<Self> {
+        self.mul(Self::new(other.denominator, other.numerator)?)
+    }
+
+    fn sub(self, other: Self) -> Result<Self> {
+        let overflow = || format_err!("Overflow in linear solver");
+        Self::new(
+            self.numerator
+                .checked_mul(other.denominator)
+                .ok_or_else(overflow)?
+                .checked_sub(
+                    other
+                        .numerator
+                        .checked_mul(self.denominator)
+                        .ok_or_else(overflow)?,
+                )
+                .ok_or_else(overflow)?,
+            self.denominator
+                .checked_mul(other.denominator)
+                .ok_or_else(overflow)?,
+        )
+    }
+
+    fn as_integer(&self) -> Option<i64> {
+        if self.denominator == 1
+            && self.numerator >= i64::MIN as i128
+            && self.numerator <= i64::MAX as i128
+        {
+            Some(self.numerator as i64)
+        } else {
+            None
+        }
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_solve_memory_goal() -> Result<()> {
+        // [0] = [9] * 3 + [10]
+        let tape = Tape::from_str("1002,9,3,0,1,0,10,0,99,0,0")?;
+        let mut executor = SymbolicExecutor::new(&tape);
+        executor.symbolic_memory(9, "a", 0, 99);
+        executor.symbolic_memory(10, "b", 0, 2);
+
+        let solutions = executor.solve_all(
+            &Goal::Memory {
+                address: 0,
+                value: 31,
+            },
+            10,
+        )?;
+        let found: Vec<(i64, i64)> = solutions
+            .iter()
+            .map(|s| (s.value("a").unwrap(), s.value("b").unwrap()))
+            .collect();
+
+        assert_eq!(found, vec![(10, 1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_forks_on_comparison() -> Result<()> {
+        // Outputs 1 if input < 10, else 2
+        let tape = Tape::from_str("3,20,1007,20,10,21,1005,21,13,104,2,99,0,104,1,99")?;
+        let mut executor = SymbolicExecutor::new(&tape);
+        executor.symbolic_input("x", 0, 20);
+
+        let small = executor.solve_all(&Goal::Output { index: 0, value: 1 }, 100)?;
+        assert_eq!(small.len(), 10);
+        assert!(small.iter().all(|s| s.value("x").unwrap() < 10));
+
+        let large = executor.solve_all(&Goal::Output { index: 0, value: 2 }, 100)?;
+        assert_eq!(large.len(), 11);
+        assert!(large.iter().all(|s| s.value("x").unwrap() >= 10));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve_no_solutions_wanted() -> Result<()> {
+        let tape = Tape::from_str("1002,9,3,0,1,0,10,0,99,0,0")?;
+        let mut executor = SymbolicExecutor::new(&tape);
+        executor.symbolic_memory(9, "a", 0, 99);
+
+        let goal = Goal::Memory {
+            address: 0,
+            value: 30,
+        };
+        assert!(executor.solve_all(&goal, 0)?.is_empty());
+        assert_eq!(executor.solve_all(&goal, 1)?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inconsistent_system() -> Result<()> {
+        // [0] = [5] + [5] can never be odd
+        let tape = Tape::from_str("1,5,5,0,99,0")?;
+        let mut executor = SymbolicExecutor::new(&tape);
+        executor.symbolic_memory(5, "a", -100, 100);
+
+        assert!(executor
+            .solve(&Goal::Memory {
+                address: 0,
+                value: 7
+            })?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_base_overflow_aborts_path() -> Result<()> {
+        // Moves the relative base past i64::MAX, then writes relative to it
+        let tape = Tape::from_str("109,9223372036854775807,109,1,203,0,99")?;
+        let mut executor = SymbolicExecutor::new(&tape);
+        executor.symbolic_input("x", 0, 9);
+
+        assert!(executor
+            .solve(&Goal::Memory {
+                address: 0,
+                value: 0
+            })?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_address_overflow_aborts_path() -> Result<()> {
+        let tape = Tape::from_str("109,9223372036854775807,203,1,99")?;
+        let mut executor = SymbolicExecutor::new(&tape);
+        executor.symbolic_input("x", 0, 9);
+
+        assert!(executor
+            .solve(&Goal::Memory {
+                address: 0,
+                value: 109
+            })?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_range_exceeds_search_limit() -> Result<()> {
+        // [0] = [5] + [6], so one of them is left free over its whole range
+        let tape = Tape::from_str("1,5,6,0,99,0,0")?;
+        let mut executor = SymbolicExecutor::new(&tape);
+        executor.symbolic_memory(5, "a", i64::MIN, i64::MAX);
+        executor.symbolic_memory(6, "b", i64::MIN, i64::MAX);
+
+        assert!(executor
+            .solve(&Goal::Memory {
+                address: 0,
+                value: 0
+            })
+            .is_err());
+
+        Ok(())
+    }
+}
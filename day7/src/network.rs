@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+
+use anyhow::{format_err, Result};
+use log::debug;
+
+use crate::intcode::{Program, ProgramState, Tape};
+
+// Instructions a machine may run per turn, so a machine spinning without
+// reading input can't starve the others
+const QUANTUM: u64 = 10_000;
+
+// Instructions the whole network may run in one call to `run`, unless told
+// otherwise
+const BUDGET: u64 = 100_000_000;
+
+struct Machine {
+    name: String,
+    program: Program,
+    inputs: VecDeque<i64>,
+    last_output: Option<i64>,
+}
+
+// A directed connection from one machine's output to another's input. Every
+// value a machine outputs is sent down each of its outgoing links.
+#[derive(Debug)]
+pub struct Link {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    pub sent: usize,
+    pub last: Option<i64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum NetworkState {
+    // Every machine has terminated
+    Halted,
+    // Every machine still running is waiting for input nothing in the network
+    // will provide. Only `send` can get things moving again.
+    Deadlocked { waiting: Vec<String> },
+    // The budget ran out with these machines still busy, most likely looping
+    // without ever needing input
+    OutOfBudget { running: Vec<String> },
+}
+
+pub struct Network {
+    machines: Vec<Machine>,
+    links: Vec<Link>,
+    budget: u64,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::with_budget(BUDGET)
+    }
+
+    // `budget` caps the instructions each call to `run` may use in total
+    pub fn with_budget(budget: u64) -> Self {
+        Self {
+            machines: Vec::new(),
+            links: Vec::new(),
+            budget,
+        }
+    }
+
+    fn machine_index(&self, name: &str) -> Result<usize> {
+        self.machines
+            .iter()
+            .position(|machine| machine.name == name)
+            .ok_or(format_err!("No machine named \"{}\"", name))
+    }
+
+    // Machines take turns in the order they were added
+    pub fn add_machine(&mut self, name: &str, tape: &Tape) -> Result<()> {
+        if self.machine_index(name).is_ok() {
+            return Err(format_err!("Machine \"{}\" already exists", name));
+        }
+
+        self.machines.push(Machine {
+            name: name.to_string(),
+            program: Program::new(tape),
+            inputs: VecDeque::new(),
+            last_output: None,
+        });
+
+        Ok(())
+    }
+
+    pub fn connect(&mut self, name: &str, from: &str, to: &str) -> Result<()> {
+        if self.links.iter().any(|link| link.name == name) {
+            return Err(format_err!("Link \"{}\" already exists", name));
+        }
+        self.machine_index(from)?;
+        self.machine_index(to)?;
+
+        self.links.push(Link {
+            name: name.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            sent: 0,
+            last: None,
+        });
+
+        Ok(())
+    }
+
+    // Queues a value for a machine from outside the network
+    pub fn send(&mut self, to: &str, value: i64) -> Result<()> {
+        let index = self.machine_index(to)?;
+        self.machines[index].inputs.push_back(value);
+
+        Ok(())
+    }
+
+    // The most recent value a machine output, whether or not it went anywhere
+    pub fn last_output(&self, name: &str) -> Result<Option<i64>> {
+        Ok(self.machines[self.machine_index(name)?].last_output)
+    }
+
+    pub fn link(&self, name: &str) -> Option<&Link> {
+        self.links.iter().find(|link| link.name == name)
+    }
+
+    pub fn links(&self) -> impl Iterator<Item = &Link> {
+        self.links.iter()
+    }
+
+    fn deliver(&mut self, from: &str, outputs: VecDeque<i64>) -> Result<()> {
+        for value in outputs {
+            let mut targets = Vec::new();
+            for link in self.links.iter_mut().filter(|link| link.from == from) {
+                debug!("[{}] {} -> {}: {}", link.name, link.from, link.to, value);
+                link.sent += 1;
+                link.last = Some(value);
+                targets.push(link.to.clone());
+            }
+
+            for target in targets {
+                self.send(&target, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Runs one turn of every machine, returning how many instructions they ran
+    fn round(&mut self) -> Result<u64> {
+        let mut ran = 0;
+
+        for i in 0..self.machines.len() {
+            let machine = &mut self.machines[i];
+            if machine.program.state == ProgramState::Terminated {
+                continue;
+            }
+
+            let before = machine.program.instruction_count;
+            let outputs = machine
+                .program
+                .run_to_next_input(&mut machine.inputs, QUANTUM)
+                .map_err(|e| format_err!("Machine \"{}\" failed: {:#}", machine.name, e))?;
+            ran += machine.program.instruction_count - before;
+            if let Some(value) = outputs.back() {
+                machine.last_output = Some(*value);
+            }
+
+            let name = machine.name.clone();
+            self.deliver(&name, outputs)?;
+        }
+
+        Ok(ran)
+    }
+
+    fn running(&self) -> Vec<String> {
+        self.machines
+            .iter()
+            .filter(|machine| machine.program.state == ProgramState::Running)
+            .map(|machine| machine.name.clone())
+            .collect()
+    }
+
+    pub fn run(&mut self) -> Result<NetworkState> {
+        let mut spent = 0;
+        loop {
+            let ran = self.round()?;
+            if ran == 0 {
+                break;
+            }
+
+            spent += ran;
+            if spent >= self.budget {
+                let running = self.running();
+                if !running.is_empty() {
+                    return Ok(NetworkState::OutOfBudget { running });
+                }
+            }
+        }
+
+        let waiting = self.running();
+        if waiting.is_empty() {
+            Ok(NetworkState::Halted)
+        } else {
+            Ok(NetworkState::Deadlocked { waiting })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::string_to_vec;
+
+    // Reads a value and outputs it plus one, looping until that reaches 10
+    const COUNT_TO_TEN: &str = "3,16,1001,16,1,16,4,16,1007,16,10,17,1005,17,0,99,0,0";
+    // Reads a value and outputs it plus one
+    const ADD_ONE: &str = "3,9,1001,9,1,9,4,9,99,0";
+    // Reads two values and outputs their sum
+    const SUM: &str = "3,11,3,12,1,11,12,13,4,13,99,0,0,0";
+
+    fn tape(source: &str) -> Tape {
+        string_to_vec(source).unwrap()
+    }
+
+    fn traffic(network: &Network) -> Vec<(&str, usize, Option<i64>)> {
+        network
+            .links()
+            .map(|link| (link.name.as_str(), link.sent, link.last))
+            .collect()
+    }
+
+    #[test]
+    fn test_ring() -> Result<()> {
+        let mut network = Network::new();
+        network.add_machine("A", &tape(COUNT_TO_TEN))?;
+        network.add_machine("B", &tape(COUNT_TO_TEN))?;
+        network.connect("A->B", "A", "B")?;
+        network.connect("B->A", "B", "A")?;
+        network.send("A", 0)?;
+
+        assert_eq!(network.run()?, NetworkState::Halted);
+        assert_eq!(
+            traffic(&network),
+            vec![("A->B", 6, Some(11)), ("B->A", 5, Some(10))]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dag() -> Result<()> {
+        // S feeds both L and R, which both feed J
+        let mut network = Network::new();
+        network.add_machine("S", &tape(ADD_ONE))?;
+        network.add_machine("L", &tape(ADD_ONE))?;
+        network.add_machine("R", &tape(ADD_ONE))?;
+        network.add_machine("J", &tape(SUM))?;
+        network.connect("S->L", "S", "L")?;
+        network.connect("S->R", "S", "R")?;
+        network.connect("L->J", "L", "J")?;
+        network.connect("R->J", "R", "J")?;
+        network.send("S", 1)?;
+
+        assert_eq!(network.run()?, NetworkState::Halted);
+        assert_eq!(network.last_output("J")?, Some(6));
+        assert_eq!(network.link("R->J").map(|link| link.last), Some(Some(3)));
+        assert!(traffic(&network).iter().all(|(_, sent, _)| *sent == 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deadlock() -> Result<()> {
+        let mut network = Network::new();
+        network.add_machine("A", &tape(ADD_ONE))?;
+        network.add_machine("B", &tape(ADD_ONE))?;
+        network.connect("A->B", "A", "B")?;
+        network.connect("B->A", "B", "A")?;
+
+        assert_eq!(
+            network.run()?,
+            NetworkState::Deadlocked {
+                waiting: vec!["A".to_string(), "B".to_string()]
+            }
+        );
+
+        // A value from outside is enough to finish both off
+        network.send("A", 1)?;
+        assert_eq!(network.run()?, NetworkState::Halted);
+        assert_eq!(
+            traffic(&network),
+            vec![("A->B", 1, Some(2)), ("B->A", 1, Some(3))]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_budget() -> Result<()> {
+        let mut network = Network::with_budget(50_000);
+        network.add_machine("spin", &tape("1105,1,0"))?;
+        network.add_machine("done", &tape("99"))?;
+
+        assert_eq!(
+            network.run()?,
+            NetworkState::OutOfBudget {
+                running: vec!["spin".to_string()]
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{format_err, Result};
+use log::debug;
+
+use crate::amplifier_names;
+use crate::intcode::{Program, ProgramState, Tape};
+use crate::network::Link;
+
+// Instructions run between checks for new input
+const QUANTUM: u64 = 10_000;
+
+// Like `Network`, but every machine runs on its own thread. A machine stops
+// once it terminates, dropping its outgoing channels so downstream machines
+// see the disconnect instead of waiting forever.
+pub struct Pipeline {
+    machines: Vec<(String, Tape)>,
+    links: Vec<Link>,
+    inputs: Vec<(String, i64)>,
+    timeout: Duration,
+}
+
+impl Pipeline {
+    // `timeout` is how long a machine may wait for input before giving up
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            machines: Vec::new(),
+            links: Vec::new(),
+            inputs: Vec::new(),
+            timeout,
+        }
+    }
+
+    fn machine_index(&self, name: &str) -> Result<usize> {
+        self.machines
+            .iter()
+            .position(|(machine, _)| machine == name)
+            .ok_or(format_err!("No machine named \"{}\"", name))
+    }
+
+    pub fn add_machine(&mut self, name: &str, tape: &Tape) -> Result<()> {
+        if self.machine_index(name).is_ok() {
+            return Err(format_err!("Machine \"{}\" already exists", name));
+        }
+
+        self.machines.push((name.to_string(), tape.clone()));
+
+        Ok(())
+    }
+
+    pub fn connect(&mut self, name: &str, from: &str, to: &str) -> Result<()> {
+        if self.links.iter().any(|link| link.name == name) {
+            return Err(format_err!("Link \"{}\" already exists", name));
+        }
+        self.machine_index(from)?;
+        self.machine_index(to)?;
+
+        self.links.push(Link {
+            name: name.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            sent: 0,
+            last: None,
+        });
+
+        Ok(())
+    }
+
+    // Queues a value for a machine before the pipeline starts
+    pub fn send(&mut self, to: &str, value: i64) -> Result<()> {
+        self.machine_index(to)?;
+        self.inputs.push((to.to_string(), value));
+
+        Ok(())
+    }
+
+    // Runs every machine to termination, returning the traffic on each link
+    pub fn run(self) -> Result<Vec<Link>> {
+        let (senders, receivers): (Vec<Sender<i64>>, Vec<Receiver<i64>>) =
+            self.machines.iter().map(|_| channel()).unzip();
+
+        for (to, value) in self.inputs.iter() {
+            let index = self.machine_index(to)?;
+            senders[index].send(*value)?;
+        }
+
+        let mut handles = Vec::new();
+        for ((name, tape), receiver) in self.machines.iter().zip(receivers) {
+            let mut outgoing = Vec::new();
+            for link in self.links.iter().filter(|link| &link.from == name) {
+                let sender = senders[self.machine_index(&link.to)?].clone();
+                outgoing.push((
+                    Link {
+                        name: link.name.clone(),
+                        from: link.from.clone(),
+                        to: link.to.clone(),
+                        sent: 0,
+                        last: None,
+                    },
+                    sender,
+                ));
+            }
+
+            let name = name.clone();
+            let program = Program::new(tape);
+            let timeout = self.timeout;
+            handles.push((
+                name.clone(),
+                thread::spawn(move || run_machine(&name, program, receiver, outgoing, timeout)),
+            ));
+        }
+
+        // Only the links hold senders now, so channels close as machines stop
+        drop(senders);
+
+        let mut links = Vec::new();
+        let mut errors = Vec::new();
+        for (name, handle) in handles {
+            match handle.join() {
+                Ok(Ok(machine_links)) => links.extend(machine_links),
+                Ok(Err(e)) => errors.push(format!("{:#}", e)),
+                Err(_) => errors.push(format!("Machine \"{}\" panicked", name)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(format_err!("{}", errors.join("; ")));
+        }
+
+        // Report links in the order they were connected
+        links.sort_by_key(|link| self.links.iter().position(|l| l.name == link.name));
+
+        Ok(links)
+    }
+}
+
+// Same feedback loop as `search::signal`, with a thread per amplifier
+pub fn feedback_signal(tape: &Tape, sequence: &[i64]) -> Result<i64> {
+    if sequence.is_empty() {
+        return Err(format_err!("No phase settings, so no amplifiers to run"));
+    }
+
+    let names = amplifier_names(sequence.len());
+
+    let mut pipeline = Pipeline::new(Duration::from_secs(5));
+    for (name, phase) in names.iter().zip(sequence.iter()) {
+        pipeline.add_machine(name, tape)?;
+        pipeline.send(name, *phase)?;
+    }
+    for (i, name) in names.iter().enumerate() {
+        let next = &names[(i + 1) % names.len()];
+        pipeline.connect(&format!("{}->{}", name, next), name, next)?;
+    }
+    pipeline.send(&names[0], 0)?;
+
+    let thrusters = format!("{}->{}", names[names.len() - 1], names[0]);
+    pipeline
+        .run()?
+        .into_iter()
+        .find(|link| link.name == thrusters)
+        .and_then(|link| link.last)
+        .ok_or(format_err!("Nothing was sent to the thrusters"))
+}
+
+fn run_machine(
+    name: &str,
+    mut program: Program,
+    receiver: Receiver<i64>,
+    mut outgoing: Vec<(Link, Sender<i64>)>,
+    timeout: Duration,
+) -> Result<Vec<Link>> {
+    let mut inputs = VecDeque::new();
+
+    loop {
+        inputs.extend(receiver.try_iter());
+
+        let before = program.instruction_count;
+        let outputs = program
+            .run_to_next_input(&mut inputs, QUANTUM)
+            .map_err(|e| format_err!("Machine \"{}\" failed: {:#}", name, e))?;
+        let blocked = program.instruction_count - before < QUANTUM;
+
+        for value in outputs {
+            for (link, sender) in outgoing.iter_mut() {
+                debug!("[{}] {} -> {}: {}", link.name, link.from, link.to, value);
+                link.sent += 1;
+                link.last = Some(value);
+                // The receiving machine may already have terminated
+                let _ = sender.send(value);
+            }
+        }
+
+        if program.state == ProgramState::Terminated {
+            break;
+        }
+
+        if blocked && inputs.is_empty() {
+            match receiver.recv_timeout(timeout) {
+                Ok(value) => inputs.push_back(value),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format_err!(
+                        "Machine \"{}\" timed out after {:?} waiting for input",
+                        name,
+                        timeout
+                    ))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(format_err!(
+                    "Machine \"{}\" is waiting for input but every machine feeding it has stopped",
+                    name
+                ))
+                }
+            }
+        }
+    }
+
+    Ok(outgoing.into_iter().map(|(link, _)| link).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::search::{signal, Topology};
+    use super::*;
+    use crate::intcode::string_to_vec;
+
+    // Reads a value and outputs it plus one
+    const ADD_ONE: &str = "3,9,1001,9,1,9,4,9,99,0";
+
+    fn tape(source: &str) -> Tape {
+        string_to_vec(source).unwrap()
+    }
+
+    #[test]
+    fn test_matches_network() -> Result<()> {
+        let tape = tape(include_str!("../input.txt"));
+
+        // A thread per amplifier makes all 120 orders slow, so check a few
+        for sequence in [[5, 6, 7, 8, 9], [9, 8, 7, 6, 5], [7, 5, 9, 6, 8]].iter() {
+            assert_eq!(
+                feedback_signal(&tape, sequence)?,
+                signal(&tape, sequence, Topology::Feedback)?
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_phases() {
+        assert_eq!(
+            feedback_signal(&tape(ADD_ONE), &[])
+                .unwrap_err()
+                .to_string(),
+            "No phase settings, so no amplifiers to run"
+        );
+    }
+
+    #[test]
+    fn test_shutdown_on_terminate() -> Result<()> {
+        // A halts straight after answering, so B's reply has nowhere to go
+        let mut pipeline = Pipeline::new(Duration::from_secs(5));
+        pipeline.add_machine("A", &tape(ADD_ONE))?;
+        pipeline.add_machine("B", &tape(ADD_ONE))?;
+        pipeline.connect("A->B", "A", "B")?;
+        pipeline.connect("B->A", "B", "A")?;
+        pipeline.send("A", 1)?;
+
+        let traffic: Vec<(String, usize, Option<i64>)> = pipeline
+            .run()?
+            .into_iter()
+            .map(|link| (link.name, link.sent, link.last))
+            .collect();
+        assert_eq!(
+            traffic,
+            vec![
+                ("A->B".to_string(), 1, Some(2)),
+                ("B->A".to_string(), 1, Some(3))
+            ]
+        );
+
+        // B can't be fed once A is gone
+        let mut pipeline = Pipeline::new(Duration::from_secs(5));
+        pipeline.add_machine("A", &tape("99"))?;
+        pipeline.add_machine("B", &tape(ADD_ONE))?;
+        pipeline.connect("A->B", "A", "B")?;
+        assert_eq!(
+            pipeline.run().unwrap_err().to_string(),
+            "Machine \"B\" is waiting for input but every machine feeding it has stopped"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_timeout_names_machine() -> Result<()> {
+        // A feeds itself, so its input never closes, but nothing starts it
+        let mut pipeline = Pipeline::new(Duration::from_millis(50));
+        pipeline.add_machine("A", &tape(ADD_ONE))?;
+        pipeline.connect("A->A", "A", "A")?;
+
+        assert_eq!(
+            pipeline.run().unwrap_err().to_string(),
+            "Machine \"A\" timed out after 50ms waiting for input"
+        );
+
+        Ok(())
+    }
+}
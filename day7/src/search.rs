@@ -0,0 +1,232 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use anyhow::{format_err, Result};
+use itertools::Itertools;
+use log::debug;
+
+use crate::amplifier_names;
+use crate::intcode::Tape;
+use crate::network::{Network, NetworkState};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Topology {
+    // The last amplifier's output is the signal
+    Serial,
+    // The last amplifier also feeds back into the first until they all halt
+    Feedback,
+}
+
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub sequence: Vec<i64>,
+    pub signal: i64,
+}
+
+// Runs one amplifier per phase, starting the first with a 0 signal
+pub fn signal(tape: &Tape, sequence: &[i64], topology: Topology) -> Result<i64> {
+    if sequence.is_empty() {
+        return Err(format_err!("No phase settings, so no amplifiers to run"));
+    }
+
+    let names = amplifier_names(sequence.len());
+
+    let mut network = Network::new();
+    for (name, phase) in names.iter().zip(sequence.iter()) {
+        network.add_machine(name, tape)?;
+        network.send(name, *phase)?;
+    }
+
+    for (i, name) in names.iter().enumerate() {
+        let next = match (names.get(i + 1), topology) {
+            (Some(next), _) => next,
+            (None, Topology::Feedback) => &names[0],
+            (None, Topology::Serial) => break,
+        };
+        network.connect(&format!("{}->{}", name, next), name, next)?;
+    }
+
+    network.send(&names[0], 0)?;
+
+    match network.run()? {
+        NetworkState::Halted => {}
+        NetworkState::Deadlocked { waiting } => {
+            return Err(format_err!(
+                "Amplifiers deadlocked waiting on {:?}",
+                waiting
+            ));
+        }
+        NetworkState::OutOfBudget { running } => {
+            return Err(format_err!("Amplifiers {:?} never finished", running));
+        }
+    }
+
+    for link in network.links() {
+        debug!(
+            "{}: {} value(s), last {:?}",
+            link.name, link.sent, link.last
+        );
+    }
+
+    let last = &names[names.len() - 1];
+    let signal = match topology {
+        Topology::Serial => network.last_output(last)?,
+        Topology::Feedback => network
+            .link(&format!("{}->{}", last, names[0]))
+            .and_then(|link| link.last),
+    };
+
+    signal.ok_or(format_err!("Nothing was sent to the thrusters"))
+}
+
+// Evaluates every ordering of `phases` across all cores, handing each
+// candidate to `keep` along with its index in permutation order
+fn evaluate_all(
+    tape: &Tape,
+    phases: &[i64],
+    topology: Topology,
+    keep: &(dyn Fn(usize, Candidate) + Sync),
+) -> Result<()> {
+    let sequences: Vec<Vec<i64>> = phases.iter().cloned().permutations(phases.len()).collect();
+    let next = AtomicUsize::new(0);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let sequence = match sequences.get(index) {
+                            Some(sequence) => sequence,
+                            None => return Ok(()),
+                        };
+                        let signal = signal(tape, sequence, topology)
+                            .map_err(|e| format_err!("Sequence {:?} failed: {:#}", sequence, e))?;
+                        keep(
+                            index,
+                            Candidate {
+                                sequence: sequence.clone(),
+                                signal,
+                            },
+                        );
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(format_err!("Search thread panicked")))
+            })
+            .collect::<Result<Vec<()>>>()
+    })?;
+
+    Ok(())
+}
+
+// Ties go to the sequence that comes first in permutation order
+pub fn best_sequence(tape: &Tape, phases: &[i64], topology: Topology) -> Result<Candidate> {
+    let best: Mutex<Option<(usize, Candidate)>> = Mutex::new(None);
+
+    evaluate_all(tape, phases, topology, &|index, candidate| {
+        let mut best = best.lock().unwrap();
+        let better = match best.as_ref() {
+            Some((best_index, best_candidate)) => {
+                (candidate.signal, std::cmp::Reverse(index))
+                    > (best_candidate.signal, std::cmp::Reverse(*best_index))
+            }
+            None => true,
+        };
+        if better {
+            *best = Some((index, candidate));
+        }
+    })?;
+
+    best.into_inner()
+        .unwrap()
+        .map(|(_, candidate)| candidate)
+        .ok_or(format_err!("No phase sequences to try"))
+}
+
+// Every sequence from strongest signal to weakest
+pub fn rank_sequences(tape: &Tape, phases: &[i64], topology: Topology) -> Result<Vec<Candidate>> {
+    let candidates = Mutex::new(Vec::new());
+
+    evaluate_all(tape, phases, topology, &|index, candidate| {
+        candidates.lock().unwrap().push((index, candidate));
+    })?;
+
+    let mut candidates = candidates.into_inner().unwrap();
+    candidates.sort_by_key(|(index, candidate)| (std::cmp::Reverse(candidate.signal), *index));
+
+    Ok(candidates
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::string_to_vec;
+
+    #[test]
+    fn test_serial_example() -> Result<()> {
+        let tape = string_to_vec("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0")?;
+
+        let best = best_sequence(&tape, &[0, 1, 2, 3, 4], Topology::Serial)?;
+        assert_eq!(best.sequence, vec![4, 3, 2, 1, 0]);
+        assert_eq!(best.signal, 43210);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_feedback_example() -> Result<()> {
+        let tape = string_to_vec(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        )?;
+
+        let best = best_sequence(&tape, &[5, 6, 7, 8, 9], Topology::Feedback)?;
+        assert_eq!(best.sequence, vec![9, 8, 7, 6, 5]);
+        assert_eq!(best.signal, 139629729);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_phases() -> Result<()> {
+        let tape = string_to_vec("3,0,4,0,99")?;
+
+        for topology in [Topology::Serial, Topology::Feedback].iter() {
+            assert_eq!(
+                signal(&tape, &[], *topology).unwrap_err().to_string(),
+                "No phase settings, so no amplifiers to run"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ranking() -> Result<()> {
+        let tape = string_to_vec(include_str!("../input.txt"))?;
+
+        let ranking = rank_sequences(&tape, &[5, 6, 7, 8, 9], Topology::Feedback)?;
+        assert_eq!(ranking.len(), 120);
+        assert!(ranking
+            .windows(2)
+            .all(|pair| pair[0].signal >= pair[1].signal));
+
+        let best = best_sequence(&tape, &[5, 6, 7, 8, 9], Topology::Feedback)?;
+        assert_eq!(ranking[0].sequence, best.sequence);
+        assert_eq!(ranking[0].signal, best.signal);
+
+        Ok(())
+    }
+}
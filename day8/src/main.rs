@@ -1,6 +1,9 @@
+#[path = "../../common/ocr.rs"]
+mod ocr;
+
 use std::collections::BTreeMap;
 
-use anyhow::Result;
+use anyhow::{format_err, Context, Result};
 
 fn print_layer(layer: &str, width: usize, height: usize) {
     for y in 0..height {
@@ -30,6 +33,8 @@ fn read_input(filename: &str) -> Result<String> {
     Ok(std::fs::read_to_string(filename)?.clone())
 }
 
+include!("../../common/args.rs");
+
 fn main() -> Result<()> {
     let image = read_input("input.txt")?;
 
@@ -68,5 +73,18 @@ fn main() -> Result<()> {
 
     print_layer(&result, width, height);
 
+    let rows: Vec<Vec<bool>> = (0..height)
+        .map(|y| result[y * width..(y + 1) * width].chars().map(|c| c == '1').collect())
+        .collect();
+    let message = ocr::recognize(&rows).context("Unable to read the message")?;
+    println!("Message: {}", message);
+
+    // Lets a run double as a check on the decoder
+    if let Some(expected) = flag_value("--expect") {
+        if message != expected {
+            return Err(format_err!("Expected \"{}\", read \"{}\"", expected, message));
+        }
+    }
+
     Ok(())
 }
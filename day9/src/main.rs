@@ -1,499 +1,86 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::VecDeque;
 use std::convert::{TryFrom, TryInto};
 
-use anyhow::{format_err, Context, Error, Result};
-use itertools::Itertools;
-use log::{debug, info, trace};
+use anyhow::{format_err, Result};
+use log::info;
 
-fn string_to_vec(input: &str) -> Result<Tape> {
-    let mut ret = Vec::new();
-    for num in input.trim().split(",") {
-        ret.push(num.parse()?);
-    }
-    Ok(Tape::new(&ret))
-}
-
-fn read_input(filename: &str) -> Result<Tape> {
-    let data = std::fs::read_to_string(filename)?;
-
-    string_to_vec(&data)
-}
-
-#[derive(Debug)]
-enum OpCode {
-    Add,
-    Multiply,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    Terminate,
-    AdjustRelativeBase,
-}
-
-impl OpCode {
-    fn argument_count(&self) -> usize {
-        match self {
-            OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => 3,
-            OpCode::JumpIfTrue | OpCode::JumpIfFalse => 2,
-            OpCode::Input | OpCode::Output | OpCode::AdjustRelativeBase => 1,
-            OpCode::Terminate => 0,
-        }
-    }
-}
-
-impl TryFrom<i64> for OpCode {
-    type Error = Error;
-
-    fn try_from(value: i64) -> Result<Self, Self::Error> {
-        match value {
-            1 => Ok(OpCode::Add),
-            2 => Ok(OpCode::Multiply),
-            3 => Ok(OpCode::Input),
-            4 => Ok(OpCode::Output),
-            5 => Ok(OpCode::JumpIfTrue),
-            6 => Ok(OpCode::JumpIfFalse),
-            7 => Ok(OpCode::LessThan),
-            8 => Ok(OpCode::Equals),
-            9 => Ok(OpCode::AdjustRelativeBase),
-            99 => Ok(OpCode::Terminate),
-            _ => Err(format_err!("Unknown opcode {}", value)),
-        }
-    }
-}
-
-impl TryFrom<&str> for OpCode {
-    type Error = Error;
-
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let value_i: i64 = value
-            .parse()
-            .with_context(|| format!("Failed to parse opcode string into i64: \"{}\"", value))?;
-        value_i.try_into()
-    }
-}
-
-#[derive(Clone)]
-struct Tape {
-    memory: BTreeMap<usize, i64>,
-    relative_base: i64,
-}
-
-impl Tape {
-    fn new(program: &Vec<i64>) -> Self {
-        let mut tape = Tape {
-            memory: BTreeMap::new(),
-            relative_base: 0,
-        };
-        for (i, item) in program.iter().enumerate() {
-            tape.memory.insert(i, *item);
-        }
-
-        tape
-    }
-
-    fn get(&self, offset: usize) -> Option<i64> {
-        self.memory.get(&offset).or(Some(&0)).cloned()
-    }
-
-    fn set(&mut self, offset: usize, value: i64) -> Result<()> {
-        trace!("[SET] [{}] = {}", offset, value);
-
-        self.memory.insert(offset, value);
-
-        Ok(())
-    }
-
-    fn get_relative_base(&self) -> i64 {
-        self.relative_base
-    }
-
-    fn set_relative_base(&mut self, new_base: i64) {
-        self.relative_base = new_base;
-    }
-}
-
-#[derive(Debug)]
-enum FetchMode {
-    Immediate,
-    Position,
-    Relative,
-}
-
-impl TryFrom<char> for FetchMode {
-    type Error = Error;
-
-    fn try_from(value: char) -> Result<Self, Self::Error> {
-        match value {
-            '0' => Ok(FetchMode::Position),
-            '1' => Ok(FetchMode::Immediate),
-            '2' => Ok(FetchMode::Relative),
-            _ => Err(format_err!("Unknown mode {}", value)),
-        }
-    }
-}
-
-#[derive(Debug)]
-struct Argument {
-    mode: FetchMode,
-    value: i64,
-}
-
-impl Argument {
-    fn get(&self, tape: &Tape, relative_base: i64) -> Option<i64> {
-        match self.mode {
-            FetchMode::Immediate => Some(self.value),
-            FetchMode::Position => tape.get(self.value as usize),
-            FetchMode::Relative => tape.get((self.value + relative_base) as usize),
-        }
-    }
-
-    fn get_for_set(&self, relative_base: i64) -> i64 {
-        match self.mode {
-            FetchMode::Relative => self.value + relative_base,
-            _ => self.value,
-        }
-    }
-}
-
-#[derive(Debug)]
-struct Instruction {
-    position: usize,
-    opcode: OpCode,
-    arguments: Vec<Argument>,
-}
+mod intcode;
 
-impl Instruction {
-    fn new(tape: &Tape, offset: usize) -> Result<Self> {
-        let code = format!(
-            "{:0>2}",
-            tape.get(offset)
-                .ok_or(format_err!("No opcode found at offset {}", offset))?
-        );
+use intcode::{read_input, FetchMode, OpCode, Program, Tape};
 
-        let opcode: OpCode = code[code.len() - 2..code.len()]
-            .try_into()
-            .with_context(|| format!("Failed to parse opcode \"{}\"", code))?;
+// BOOST reports a broken instruction by outputting its opcode along with the
+// parameter modes it was tested with, e.g. 203 for an input in relative mode
+fn describe_report(value: i64) -> String {
+    let opcode = match OpCode::try_from(value % 100) {
+        Ok(opcode) => opcode,
+        Err(e) => return format!("{}: not an instruction we can decode ({})", value, e),
+    };
 
-        let argument_count = opcode.argument_count();
-        let mut arguments = Vec::new();
-        for (i, c) in format!(
-            "{:0>width$}",
-            &code[..code.len() - 2],
-            width = argument_count,
-        )
+    let modes = format!(
+        "{:0>width$}",
+        value / 100,
+        width = opcode.argument_count()
+    );
+    let modes: Result<Vec<String>> = modes
         .chars()
         .rev()
+        .take(opcode.argument_count())
         .enumerate()
-        {
-            arguments.push(Argument {
-                mode: c
-                    .try_into()
-                    .with_context(|| format!("Failed to parse mode \"{}\"", c))?,
-                value: tape.get(offset + i + 1).unwrap(),
-            })
-        }
-
-        let instruction = Instruction {
-            position: offset,
-            opcode,
-            arguments,
-        };
-
-        instruction
-            .validate()
-            .context("Instruction failed validation")?;
-
-        Ok(instruction)
-    }
-
-    fn validate(&self) -> Result<()> {
-        let expected_argument_count = self.opcode.argument_count();
-
-        if self.arguments.len() != expected_argument_count {
-            return Err(format_err!(
-                "Expected {} argument(s), got {}",
-                expected_argument_count,
-                self.arguments.len()
-            ));
-        }
-
-        Ok(())
-    }
-
-    fn get_argument(&self, index: usize) -> Result<&Argument> {
-        self.arguments.get(index).ok_or(format_err!(
-            "Argument {} not found for opcode {:?}",
-            index + 1,
-            self.opcode
-        ))
-    }
-
-    fn get_argument_value(&self, tape: &Tape, index: usize) -> Result<i64> {
-        self.get_argument(index)?
-            .get(tape, tape.get_relative_base())
-            .ok_or(format_err!(
-                "Argument {} for opcode {:?} is None",
-                index + 1,
-                self.opcode
-            ))
-    }
-
-    fn get_argument_value_for_set(&self, tape: &Tape, index: usize) -> Result<i64> {
-        Ok(self
-            .get_argument(index)?
-            .get_for_set(tape.get_relative_base()))
-    }
-
-    fn run(
-        &self,
-        tape: &mut Tape,
-        inputs: &mut VecDeque<i64>,
-        outputs: &mut VecDeque<i64>,
-    ) -> Result<InstructionResult> {
-        trace!("{:?}", self);
-        let default_next_offset = self.position + self.opcode.argument_count() + 1;
-        match self.opcode {
-            OpCode::Add => {
-                let arg1 = self.get_argument_value(tape, 0)?;
-                let arg2 = self.get_argument_value(tape, 1)?;
-                let result_offset = self.get_argument_value_for_set(tape, 2)?;
-                let result = arg1 + arg2;
-
-                trace!(
-                    "[ADD] {} + {} = {}, [{}]",
-                    arg1,
-                    arg2,
-                    result,
-                    result_offset
-                );
-
-                tape.set(result_offset as usize, result).with_context(|| {
-                    format!(
-                        "Failed to set multiplied value {} to tape index {}",
-                        result, result_offset
-                    )
-                })?;
-
-                Ok(InstructionResult::Continue {
-                    next_offset: default_next_offset,
-                    relative_base: tape.get_relative_base(),
-                })
-            }
-            OpCode::Multiply => {
-                let arg1 = self.get_argument_value(tape, 0)?;
-                let arg2 = self.get_argument_value(tape, 1)?;
-                let result_offset = self.get_argument_value_for_set(tape, 2)?;
-
-                let result = arg1 * arg2;
+        .map(|(i, c)| {
+            let mode: FetchMode = c.try_into()?;
+            Ok(format!("parameter {} in {:?} mode", i + 1, mode))
+        })
+        .collect();
 
-                trace!(
-                    "[MUL] {} * {} = {}, [{}]",
-                    arg1,
-                    arg2,
-                    result,
-                    result_offset
-                );
-
-                tape.set(result_offset as usize, result).with_context(|| {
-                    format!(
-                        "Failed to set multiplied value {} to tape index {}",
-                        result, result_offset
-                    )
-                })?;
-
-                Ok(InstructionResult::Continue {
-                    next_offset: default_next_offset,
-                    relative_base: tape.get_relative_base(),
-                })
-            }
-            OpCode::Input => {
-                let value = inputs
-                    .pop_front()
-                    .ok_or(format_err!("No input values left to consume"))?;
-                let result_offset = self.get_argument_value_for_set(tape, 0)?;
-
-                trace!("[INP] {} -> [{}]", value, result_offset);
-                info!("[INP] {} -> [{}]", value, result_offset);
-
-                tape.set(result_offset as usize, value).with_context(|| {
-                    format!(
-                        "Failed to set input value {} to tape index {}",
-                        value, result_offset
-                    )
-                })?;
-
-                Ok(InstructionResult::Continue {
-                    next_offset: default_next_offset,
-                    relative_base: tape.get_relative_base(),
-                })
-            }
-            OpCode::Output => {
-                outputs.push_back(self.get_argument_value(tape, 0)?);
-
-                Ok(InstructionResult::Continue {
-                    next_offset: default_next_offset,
-                    relative_base: tape.get_relative_base(),
-                })
-            }
-            OpCode::JumpIfTrue => {
-                let arg1 = self.get_argument_value(tape, 0)?;
-                let arg2 = self.get_argument_value(tape, 1)?;
-
-                Ok(InstructionResult::Continue {
-                    next_offset: if arg1 == 0 {
-                        default_next_offset
-                    } else {
-                        arg2 as usize
-                    },
-                    relative_base: tape.get_relative_base(),
-                })
-            }
-            OpCode::JumpIfFalse => {
-                let arg1 = self.get_argument_value(tape, 0)?;
-                let arg2 = self.get_argument_value(tape, 1)?;
-
-                Ok(InstructionResult::Continue {
-                    next_offset: if arg1 == 0 {
-                        arg2 as usize
-                    } else {
-                        default_next_offset
-                    },
-                    relative_base: tape.get_relative_base(),
-                })
-            }
-            OpCode::LessThan => {
-                let arg1 = self.get_argument_value(tape, 0)?;
-                let arg2 = self.get_argument_value(tape, 1)?;
-                let result_offset = self.get_argument_value_for_set(tape, 2)?;
-
-                let value = if arg1 < arg2 { 1 } else { 0 };
-
-                tape.set(result_offset as usize, value).with_context(|| {
-                    format!(
-                        "Failed to set less than value {} to tape index {}",
-                        value, result_offset
-                    )
-                })?;
-
-                Ok(InstructionResult::Continue {
-                    next_offset: default_next_offset,
-                    relative_base: tape.get_relative_base(),
-                })
-            }
-            OpCode::Equals => {
-                let arg1 = self.get_argument_value(tape, 0)?;
-                let arg2 = self.get_argument_value(tape, 1)?;
-                let result_offset = self.get_argument_value_for_set(tape, 2)?;
-
-                let value = if arg1 == arg2 { 1 } else { 0 };
-
-                tape.set(result_offset as usize, value).with_context(|| {
-                    format!(
-                        "Failed to set less than value {} to tape index {}",
-                        value, result_offset
-                    )
-                })?;
-
-                Ok(InstructionResult::Continue {
-                    next_offset: default_next_offset,
-                    relative_base: tape.get_relative_base(),
-                })
-            }
-            OpCode::AdjustRelativeBase => {
-                let arg = self.get_argument_value(tape, 0)?;
-                let relative_base = tape.get_relative_base() + arg;
-                Ok(InstructionResult::Continue {
-                    next_offset: default_next_offset,
-                    relative_base: relative_base,
-                })
-            }
-            OpCode::Terminate => Ok(InstructionResult::Terminate),
-        }
+    match modes {
+        Ok(modes) if modes.is_empty() => format!("{}: {:?} is broken", value, opcode),
+        Ok(modes) => format!("{}: {:?} with {} is broken", value, opcode, modes.join(", ")),
+        Err(e) => format!("{}: {:?} with a mode we can't decode ({})", value, opcode, e),
     }
 }
 
-enum InstructionResult {
-    Continue {
-        next_offset: usize,
-        relative_base: i64,
-    },
-    Terminate,
-}
+// Whether `value` is an opcode with valid modes for each of its parameters
+// and nothing else. The keycode never is, so a lone output that is must be a
+// report.
+fn is_report(value: i64) -> bool {
+    let opcode = match OpCode::try_from(value % 100) {
+        Ok(opcode) => opcode,
+        Err(_) => return false,
+    };
 
-struct Program {
-    tape: Tape,
-    pc: usize,
+    let modes = (value / 100).to_string();
+    modes == "0"
+        || (modes.len() <= opcode.argument_count()
+            && modes.chars().all(|c| FetchMode::try_from(c).is_ok()))
 }
 
-impl Program {
-    fn new(tape: &Tape) -> Self {
-        Program {
-            tape: tape.clone(),
-            pc: 0,
-        }
-    }
-
-    fn run_to_next_output(&mut self, inputs: &mut VecDeque<i64>) -> Result<Option<i64>> {
-        let mut outputs = VecDeque::new();
-
-        let mut instruction_count = 0;
-        loop {
-            let starting_len = outputs.len();
-            let instruction = Instruction::new(&self.tape, self.pc)
-                .with_context(|| format!("Failed to build instruction at offset {}", self.pc))?;
-
-            match instruction
-                .run(&mut self.tape, inputs, &mut outputs)
-                .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?
-            {
-                InstructionResult::Continue {
-                    next_offset,
-                    relative_base,
-                } => {
-                    self.pc = next_offset;
-                    self.tape.set_relative_base(relative_base);
-                    if outputs.len() > starting_len {
-                        break;
-                    }
-                }
-                InstructionResult::Terminate => break,
-            }
-
-            instruction_count += 1;
-        }
-
-        debug!("Ran {} instruction(s)", instruction_count);
+// Runs BOOST in test mode, where a working VM outputs nothing but the keycode
+fn self_test(tape: &Tape) -> Result<i64> {
+    let mut program = Program::new(tape);
 
-        Ok(outputs.back().cloned())
-    }
-
-    fn run(&mut self, inputs: &mut VecDeque<i64>) -> Result<VecDeque<i64>> {
-        // TODO(jsvana): make this not duplicated
-        let mut outputs = VecDeque::new();
-
-        loop {
-            let instruction = Instruction::new(&self.tape, self.pc)
-                .with_context(|| format!("Failed to build instruction at offset {}", self.pc))?;
-
-            match instruction
-                .run(&mut self.tape, inputs, &mut outputs)
-                .with_context(|| format!("Failed to run instruction at offset {}", self.pc))?
-            {
-                InstructionResult::Continue {
-                    next_offset,
-                    relative_base,
-                } => {
-                    self.pc = next_offset;
-                    self.tape.set_relative_base(relative_base);
-                }
-                InstructionResult::Terminate => break,
-            }
+    let mut inputs = VecDeque::new();
+    inputs.push_back(1);
+
+    let outputs: Vec<i64> = program.run(&mut inputs)?.into_iter().collect();
+    match outputs.as_slice() {
+        [keycode] if !is_report(*keycode) => Ok(*keycode),
+        [] => Err(format_err!("BOOST halted without any output")),
+        outputs => {
+            // BOOST may still finish with a keycode after reporting
+            let reports = match outputs.split_last() {
+                Some((last, reports)) if !is_report(*last) => reports,
+                _ => outputs,
+            };
+            let broken: Vec<String> = reports
+                .iter()
+                .map(|value| describe_report(*value))
+                .collect();
+            Err(format_err!(
+                "BOOST found {} broken instruction(s):\n{}",
+                broken.len(),
+                broken.join("\n")
+            ))
         }
-
-        Ok(outputs)
     }
 }
 
@@ -501,6 +88,13 @@ fn main() -> Result<()> {
     env_logger::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
     let tape = read_input("input.txt")?;
+
+    if std::env::args().any(|arg| arg == "--self-test") {
+        let keycode = self_test(&tape)?;
+        info!("BOOST self-test passed, keycode {}", keycode);
+        return Ok(());
+    }
+
     let mut program = Program::new(&tape);
 
     let mut inputs = VecDeque::new();
@@ -512,3 +106,65 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::intcode::string_to_vec;
+
+    #[test]
+    fn test_describe_report() {
+        assert_eq!(
+            describe_report(203),
+            "203: Input with parameter 1 in Relative mode is broken"
+        );
+        assert_eq!(
+            describe_report(1208),
+            "1208: Equals with parameter 1 in Relative mode, parameter 2 in Immediate mode, \
+             parameter 3 in Position mode is broken"
+        );
+        assert_eq!(describe_report(99), "99: Terminate is broken");
+        assert_eq!(
+            describe_report(42),
+            "42: not an instruction we can decode (Unknown opcode 42)"
+        );
+    }
+
+    #[test]
+    fn test_is_report() {
+        assert!(is_report(203));
+        assert!(is_report(1208));
+        assert!(is_report(99));
+        assert!(!is_report(42));
+        // Too many modes for an input, and a mode that doesn't exist
+        assert!(!is_report(22203));
+        assert!(!is_report(304));
+    }
+
+    #[test]
+    fn test_self_test() -> Result<()> {
+        let keycode = self_test(&string_to_vec(include_str!("../input.txt"))?)?;
+        assert!(!is_report(keycode));
+
+        // A lone output that decodes as an instruction is a report
+        assert_eq!(
+            self_test(&string_to_vec("104,203,99")?)
+                .unwrap_err()
+                .to_string(),
+            "BOOST found 1 broken instruction(s):\n\
+             203: Input with parameter 1 in Relative mode is broken"
+        );
+        assert_eq!(
+            self_test(&string_to_vec("104,1208,104,203,104,123456789,99")?)
+                .unwrap_err()
+                .to_string(),
+            "BOOST found 2 broken instruction(s):\n\
+             1208: Equals with parameter 1 in Relative mode, parameter 2 in Immediate mode, \
+             parameter 3 in Position mode is broken\n\
+             203: Input with parameter 1 in Relative mode is broken"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,422 @@
+// Runs every Intcode VM in the repo on the same programs and reports where
+// they disagree. The VMs themselves are in vms.rs.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use anyhow::{Context, Result};
+use intcode_tape::parse_values;
+
+pub mod vms;
+
+// Enough for every puzzle input in the repo with plenty of headroom
+const MAX_STEPS: usize = 10_000_000;
+
+#[derive(Clone, Debug)]
+pub enum Termination {
+    Halted,
+    Error(String),
+    StepLimit,
+    Panic(String),
+}
+
+impl Termination {
+    // Error messages differ between copies, so only the kind is compared
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Termination::Halted => "halted",
+            Termination::Error(_) => "error",
+            Termination::StepLimit => "step limit",
+            Termination::Panic(_) => "panic",
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Termination::Halted | Termination::StepLimit => write!(f, "{}", self.kind()),
+            Termination::Error(message) | Termination::Panic(message) => {
+                write!(f, "{} ({})", self.kind(), message)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Outcome {
+    pub outputs: Vec<i64>,
+    // Only non-zero cells, since unset and zero are the same to a program
+    memory: BTreeMap<usize, i64>,
+    pub termination: Termination,
+}
+
+pub fn normalize(cells: impl Iterator<Item = (usize, i64)>) -> BTreeMap<usize, i64> {
+    cells.filter(|(_, value)| *value != 0).collect()
+}
+
+// Runs `step` until it reports termination, fails, or runs too long
+pub fn run_steps(mut step: impl FnMut() -> Result<bool>) -> Termination {
+    for _ in 0..MAX_STEPS {
+        match step() {
+            Ok(true) => {}
+            Ok(false) => return Termination::Halted,
+            Err(e) => return Termination::Error(e.to_string()),
+        }
+    }
+
+    Termination::StepLimit
+}
+
+pub type Runner = fn(&[i64], &[i64]) -> Outcome;
+
+pub struct Vm {
+    pub name: &'static str,
+    // Relative mode and memory past the end of the program, both added in day 9
+    pub extended: bool,
+    pub run: Runner,
+}
+
+impl Vm {
+    pub fn new(name: &'static str, extended: bool, run: Runner) -> Self {
+        Self {
+            name,
+            extended,
+            run,
+        }
+    }
+}
+
+pub struct Case {
+    pub name: String,
+    program: Vec<i64>,
+    inputs: Vec<i64>,
+    pub extended: bool,
+}
+
+impl Case {
+    // Only checked against VMs with day 9 features
+    fn extended(mut self) -> Self {
+        self.extended = true;
+        self
+    }
+}
+
+fn parse_program(source: &str) -> Result<Vec<i64>> {
+    parse_values(source)
+        .with_context(|| format!("Failed to parse program \"{:.40}\"", source.trim()))
+}
+
+fn file_case(name: &str, filename: &str, inputs: &[i64]) -> Result<Case> {
+    let source = std::fs::read_to_string(filename)
+        .with_context(|| format!("Failed to read {}", filename))?;
+    Ok(Case {
+        name: name.to_string(),
+        program: parse_program(&source)?,
+        inputs: inputs.to_vec(),
+        extended: false,
+    })
+}
+
+fn inline_case(name: &str, source: &str, inputs: &[i64]) -> Result<Case> {
+    Ok(Case {
+        name: name.to_string(),
+        program: parse_program(source)?,
+        inputs: inputs.to_vec(),
+        extended: false,
+    })
+}
+
+// day15's movement commands for a route written as compass letters
+fn moves(route: &str) -> Vec<i64> {
+    route
+        .chars()
+        .filter_map(|c| match c {
+            'N' => Some(1),
+            'S' => Some(2),
+            'W' => Some(3),
+            'E' => Some(4),
+            _ => None,
+        })
+        .collect()
+}
+
+// Paths are relative to this crate's directory
+pub fn corpus() -> Result<Vec<Case>> {
+    let larger_compare = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,\
+                          1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,\
+                          1105,1,46,98,99";
+
+    // A single amplifier with phase 5 wired to itself: each input after the
+    // phase is the output before it, so it loops until it halts
+    let feedback_inputs = [5, 0, 0, 4, 7, 9, 10, 20, 24, 27, 29];
+
+    // The shortest route from the start to the oxygen system. The droid never
+    // halts, so after reporting the oxygen system it stops waiting for a move.
+    let oxygen_route = moves(
+        "NNNNEENNEESSSSEEEESSWWWWWWSSEEEESSSSSSEESSWWSSEESSWWSSSSWWNNWWNNEENNNN\
+         WWNNWWNNNNNNWWNNWWNNEENNNNWWSSWWWWNNNNNNEEEESSEENNEESSEEEENNWWNNEEEENN\
+         EESSEENNEENNWWNNWWNNEEEEEEEEEESSSSWWNNWWSSSSSSSSWWSSEEEEEESSWWWWWWSSWW\
+         SSEESSSSWWWWSSEEEEEENNEESSEESSWWSSEESSWWWWWWNNWWSSSSSSEESSSSEENNNNNNEE\
+         EESSWWSSEESSWW",
+    );
+
+    Ok(vec![
+        file_case("day2 input", "../day2/input.txt", &[])?,
+        file_case("day5 input (air conditioner)", "../day5/input.txt", &[1])?,
+        file_case("day5 input (radiator)", "../day5/input.txt", &[5])?,
+        file_case("day7 input", "../day7/input.txt", &[3, 0])?,
+        file_case(
+            "day7 feedback example",
+            "../day7/test.txt",
+            &feedback_inputs,
+        )?,
+        file_case("day9 input (BOOST test)", "../day9/input.txt", &[1])?.extended(),
+        file_case("day9 large output", "../day9/test.txt", &[])?,
+        file_case("day13 input", "../day13/input.txt", &[])?.extended(),
+        file_case("day15 input", "../day15/input.txt", &oxygen_route)?.extended(),
+        inline_case("day2 example", "1,9,10,3,2,3,11,0,99,30,40,50", &[])?,
+        inline_case("day2 multiply", "2,4,4,5,99,0", &[])?,
+        inline_case("day2 overwrite", "1,1,1,4,99,5,6,0,99", &[])?,
+        inline_case("day5 immediate multiply", "1002,4,3,4,33", &[])?,
+        inline_case("day5 negative", "1101,100,-1,4,0", &[])?,
+        inline_case("day5 equal 8 (position)", "3,9,8,9,10,9,4,9,99,-1,8", &[8])?,
+        inline_case(
+            "day5 less than 8 (position)",
+            "3,9,7,9,10,9,4,9,99,-1,8",
+            &[5],
+        )?,
+        inline_case("day5 equal 8 (immediate)", "3,3,1108,-1,8,3,4,3,99", &[9])?,
+        inline_case(
+            "day5 less than 8 (immediate)",
+            "3,3,1107,-1,8,3,4,3,99",
+            &[7],
+        )?,
+        inline_case(
+            "day5 jump (position)",
+            "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+            &[0],
+        )?,
+        inline_case(
+            "day5 jump (immediate)",
+            "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+            &[3],
+        )?,
+        inline_case("day5 compare to 8 (below)", larger_compare, &[7])?,
+        inline_case("day5 compare to 8 (equal)", larger_compare, &[8])?,
+        inline_case("day5 compare to 8 (above)", larger_compare, &[9])?,
+        inline_case(
+            "day9 quine",
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            &[],
+        )?
+        .extended(),
+        inline_case("day9 16 digits", "1102,34915192,34915192,7,4,7,99,0", &[])?,
+        inline_case("missing input", "3,0,99", &[])?,
+        inline_case("bad opcode", "1,0,0,0,42", &[])?,
+        inline_case("negative address", "1101,1,1,-1,99", &[])?,
+        inline_case("negative jump", "1105,1,-7,99", &[])?,
+        inline_case("overflow", "1101,9223372036854775807,1,0,99", &[])?,
+        inline_case("truncated instruction", "1101,1", &[])?.extended(),
+    ])
+}
+
+fn first_output_difference(reference: &[i64], other: &[i64]) -> Option<String> {
+    let length = reference.len().max(other.len());
+    (0..length).find_map(|i| match (reference.get(i), other.get(i)) {
+        (a, b) if a == b => None,
+        (a, b) => Some(format!(
+            "output {} is {:?}, reference {:?} ({} vs {} outputs)",
+            i,
+            b,
+            a,
+            other.len(),
+            reference.len()
+        )),
+    })
+}
+
+fn first_memory_difference(
+    reference: &BTreeMap<usize, i64>,
+    other: &BTreeMap<usize, i64>,
+) -> Option<String> {
+    let differing: Vec<usize> = reference
+        .keys()
+        .chain(other.keys())
+        .filter(|address| reference.get(address) != other.get(address))
+        .cloned()
+        .collect::<std::collections::BTreeSet<usize>>()
+        .into_iter()
+        .collect();
+
+    let address = *differing.first()?;
+    Some(format!(
+        "memory [{}] is {}, reference {} ({} differing cell(s))",
+        address,
+        other.get(&address).unwrap_or(&0),
+        reference.get(&address).unwrap_or(&0),
+        differing.len()
+    ))
+}
+
+pub fn differences(reference: &Outcome, other: &Outcome) -> Vec<String> {
+    let mut differences = Vec::new();
+
+    if reference.termination.kind() != other.termination.kind() {
+        differences.push(format!(
+            "terminated with {}, reference {}",
+            other.termination, reference.termination
+        ));
+    }
+    differences.extend(first_output_difference(&reference.outputs, &other.outputs));
+    differences.extend(first_memory_difference(&reference.memory, &other.memory));
+
+    differences
+}
+
+// Runs one VM, turning panics into a termination of their own so one broken
+// copy can't take the whole run down
+pub fn run_guarded(run: &Runner, case: &Case) -> Outcome {
+    match panic::catch_unwind(AssertUnwindSafe(|| run(&case.program, &case.inputs))) {
+        Ok(outcome) => outcome,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| "unknown panic".to_string());
+            Outcome {
+                outputs: Vec::new(),
+                memory: BTreeMap::new(),
+                termination: Termination::Panic(message),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::format_err;
+
+    // Reads one value into cell 0 and outputs it
+    fn echo() -> Result<Case> {
+        inline_case("echo", "3,0,4,0,99", &[7])
+    }
+
+    fn run(vm: Runner, case: &Case) -> Outcome {
+        run_guarded(&Vm::new("test", true, vm).run, case)
+    }
+
+    // day15's VM with every output one too high
+    fn off_by_one(program: &[i64], inputs: &[i64]) -> Outcome {
+        let mut outcome = vms::run_day15(program, inputs);
+        for output in outcome.outputs.iter_mut() {
+            *output += 1;
+        }
+        outcome
+    }
+
+    // day15's VM, losing everything it wrote
+    fn forgetful(program: &[i64], inputs: &[i64]) -> Outcome {
+        let mut outcome = vms::run_day15(program, inputs);
+        outcome.memory.clear();
+        outcome
+    }
+
+    fn broken(_: &[i64], _: &[i64]) -> Outcome {
+        panic!("broken VM")
+    }
+
+    #[test]
+    fn test_vms_agree() -> Result<()> {
+        let case = echo()?;
+        let reference = run(vms::run_day15, &case);
+        assert_eq!(reference.outputs, vec![7]);
+
+        let others: [Runner; 7] = [
+            vms::run_day2,
+            vms::run_day5,
+            vms::run_day7,
+            vms::run_day9,
+            vms::run_day11,
+            vms::run_day13,
+            vms::run_day14,
+        ];
+        for vm in others.iter() {
+            assert!(differences(&reference, &run(*vm, &case)).is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_divergent_outputs() -> Result<()> {
+        let case = echo()?;
+
+        assert_eq!(
+            differences(&run(vms::run_day15, &case), &run(off_by_one, &case)),
+            vec!["output 0 is Some(8), reference Some(7) (1 vs 1 outputs)"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_divergent_memory() -> Result<()> {
+        let case = echo()?;
+
+        assert_eq!(
+            differences(&run(vms::run_day15, &case), &run(forgetful, &case)),
+            vec!["memory [0] is 0, reference 7 (3 differing cell(s))"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_panic_is_contained() -> Result<()> {
+        let case = echo()?;
+        let reference = run(vms::run_day15, &case);
+        let outcome = run(broken, &case);
+
+        assert_eq!(outcome.termination.to_string(), "panic (broken VM)");
+        assert_eq!(
+            differences(&reference, &outcome),
+            vec![
+                "terminated with panic (broken VM), reference halted",
+                "output 0 is None, reference Some(7) (0 vs 1 outputs)",
+                "memory [0] is 0, reference 7 (3 differing cell(s))",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_corpus_inputs_are_enough() -> Result<()> {
+        let cases = corpus()?;
+        let case = |name: &str| cases.iter().find(|case| case.name == name).unwrap();
+
+        let feedback = run(vms::run_day15, case("day7 feedback example"));
+        assert_eq!(feedback.termination.kind(), "halted");
+        assert_eq!(feedback.outputs.len(), 10);
+
+        let droid = run(vms::run_day15, case("day15 input"));
+        assert_eq!(droid.outputs.len(), 294);
+        assert_eq!(droid.outputs.last(), Some(&2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_steps() {
+        assert_eq!(run_steps(|| Ok(false)).to_string(), "halted");
+        assert_eq!(run_steps(|| Ok(true)).to_string(), "step limit");
+        assert_eq!(
+            run_steps(|| Err(format_err!("bad opcode"))).to_string(),
+            "error (bad opcode)"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::panic;
+
+use anyhow::{format_err, Result};
+
+use intcode_diff::{corpus, differences, run_guarded, vms, Vm};
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+
+    let reference_name = match args.iter().position(|a| a == "--reference") {
+        Some(i) => args
+            .get(i + 1)
+            .ok_or(format_err!("--reference needs a VM name"))?
+            .as_str(),
+        None => "day15",
+    };
+
+    let vms = [
+        Vm::new("day5", false, vms::run_day5),
+        Vm::new("day7", false, vms::run_day7),
+        Vm::new("day9", true, vms::run_day9),
+        Vm::new("day2", true, vms::run_day2),
+        Vm::new("day11", true, vms::run_day11),
+        Vm::new("day13", true, vms::run_day13),
+        Vm::new("day14", true, vms::run_day14),
+        Vm::new("day15", true, vms::run_day15),
+    ];
+
+    let reference_vm = vms
+        .iter()
+        .find(|vm| vm.name == reference_name)
+        .ok_or(format_err!("Unknown VM \"{}\"", reference_name))?;
+
+    // Panics are reported per case instead of spamming stderr
+    panic::set_hook(Box::new(|_| {}));
+
+    let cases = corpus()?;
+    let mut divergences = 0;
+    let mut skipped = 0;
+    for case in cases.iter() {
+        if case.extended && !reference_vm.extended {
+            println!("skip {} (needs day 9 features)", case.name);
+            skipped += 1;
+            continue;
+        }
+
+        let reference = run_guarded(&reference_vm.run, case);
+
+        let mut report = Vec::new();
+        for vm in vms.iter() {
+            if vm.name == reference_name || (case.extended && !vm.extended) {
+                continue;
+            }
+            let outcome = run_guarded(&vm.run, case);
+            for difference in differences(&reference, &outcome) {
+                report.push(format!("  {}: {}", vm.name, difference));
+            }
+        }
+
+        if report.is_empty() {
+            println!(
+                "ok   {} ({}, {} output(s))",
+                case.name,
+                reference.termination.kind(),
+                reference.outputs.len()
+            );
+        } else {
+            println!("DIFF {} (reference {})", case.name, reference.termination);
+            for line in report.iter() {
+                println!("{}", line);
+            }
+            divergences += 1;
+        }
+    }
+
+    let _ = panic::take_hook();
+
+    println!(
+        "{} case(s) across {} VMs, {} diverged, {} skipped",
+        cases.len(),
+        vms.len(),
+        divergences,
+        skipped
+    );
+
+    if divergences > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+// Every Intcode VM in the repo, loaded as a module straight from its day so
+// the harness exercises the real decoders, with a runner for each that steps
+// it one instruction at a time.
+//
+// day13's VM reaches its history and recorder through `super::`, and so does
+// day15's recorder, so those come along next to them.
+use std::collections::VecDeque;
+
+use crate::{normalize, run_steps, Outcome};
+
+#[path = "../../day2/src"]
+pub mod day2 {
+    pub mod intcode;
+}
+
+#[path = "../../day5/src"]
+pub mod day5 {
+    pub mod intcode;
+}
+
+#[path = "../../day7/src"]
+pub mod day7 {
+    pub mod intcode;
+}
+
+#[path = "../../day9/src"]
+pub mod day9 {
+    pub mod intcode;
+}
+
+#[path = "../../day11/src"]
+pub mod day11 {
+    pub mod intcode;
+}
+
+#[path = "../../day13/src"]
+pub mod day13 {
+    pub mod history;
+    pub mod intcode;
+    #[path = "../../common/recording.rs"]
+    pub mod recording;
+}
+
+#[path = "../../day14/src"]
+pub mod day14 {
+    pub mod intcode;
+}
+
+#[path = "../../day15/src"]
+pub mod day15 {
+    pub mod intcode;
+    // day13 has its own copy, since each one replays its own VM's program
+    #[allow(clippy::duplicate_mod)]
+    #[path = "../../common/recording.rs"]
+    pub mod recording;
+}
+
+pub fn run_day5(program: &[i64], inputs: &[i64]) -> Outcome {
+    use day5::intcode::{Instruction, InstructionResult, Tape};
+
+    let mut tape = Tape::new(program);
+    let mut inputs: VecDeque<i64> = inputs.iter().cloned().collect();
+    let mut outputs = Vec::new();
+    let mut pc = 0;
+
+    let termination = run_steps(|| {
+        let instruction = Instruction::new(&tape, pc)?;
+        match instruction.run(&mut tape, &mut inputs, &mut outputs)? {
+            InstructionResult::Continue { next_offset } => {
+                pc = next_offset;
+                Ok(true)
+            }
+            InstructionResult::Terminate => Ok(false),
+        }
+    });
+
+    Outcome {
+        outputs: outputs.into_iter().map(|output| output.value).collect(),
+        memory: normalize(tape.program.iter().cloned().enumerate()),
+        termination,
+    }
+}
+
+pub fn run_day7(program: &[i64], inputs: &[i64]) -> Outcome {
+    use day7::intcode::{Instruction, InstructionResult, Tape};
+
+    let mut tape = Tape::new(program);
+    let mut inputs: VecDeque<i64> = inputs.iter().cloned().collect();
+    let mut outputs = VecDeque::new();
+    let mut pc = 0;
+
+    let termination = run_steps(|| {
+        let instruction = Instruction::new(&tape, pc)?;
+        match instruction.run(&mut tape, &mut inputs, &mut outputs)? {
+            InstructionResult::Continue { next_offset } => {
+                pc = next_offset;
+                Ok(true)
+            }
+            InstructionResult::Terminate => Ok(false),
+        }
+    });
+
+    Outcome {
+        outputs: outputs.into_iter().collect(),
+        memory: normalize(tape.program.iter().cloned().enumerate()),
+        termination,
+    }
+}
+
+// The VMs with relative mode all step the same way
+macro_rules! relative_runner {
+    ($name:ident, $day:ident) => {
+        pub fn $name(program: &[i64], inputs: &[i64]) -> Outcome {
+            use $day::intcode::{Instruction, InstructionResult, Tape};
+
+            let mut tape = Tape::new(program);
+            let mut inputs: VecDeque<i64> = inputs.iter().cloned().collect();
+            let mut outputs = VecDeque::new();
+            let mut pc = 0;
+
+            let termination = run_steps(|| {
+                let instruction = Instruction::new(&tape, pc)?;
+                match instruction.run(&mut tape, &mut inputs, &mut outputs)? {
+                    InstructionResult::Continue {
+                        next_offset,
+                        relative_base,
+                    } => {
+                        pc = next_offset;
+                        tape.set_relative_base(relative_base);
+                        Ok(true)
+                    }
+                    InstructionResult::Terminate => Ok(false),
+                }
+            });
+
+            Outcome {
+                outputs: outputs.into_iter().collect(),
+                memory: normalize(tape.memory.iter().map(|(a, v)| (*a, *v))),
+                termination,
+            }
+        }
+    };
+}
+
+relative_runner!(run_day2, day2);
+relative_runner!(run_day9, day9);
+relative_runner!(run_day11, day11);
+relative_runner!(run_day13, day13);
+relative_runner!(run_day14, day14);
+relative_runner!(run_day15, day15);
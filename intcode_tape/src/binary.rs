@@ -0,0 +1,107 @@
+// Binary tapes start with `MAGIC`, then the number of values as a varint,
+// then every value zigzag encoded as a varint.
+
+use anyhow::{format_err, Result};
+
+pub const MAGIC: &[u8] = b"ICT1";
+
+fn write_varint(mut value: u64, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], offset: &mut usize) -> Result<u64> {
+    let start = *offset;
+    let mut value = 0;
+
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes
+            .get(*offset)
+            .ok_or(format_err!("Truncated varint at byte {}", start))?;
+        *offset += 1;
+
+        // Only the lowest bit of the tenth byte still fits in a u64
+        if shift == 63 && byte > 1 {
+            break;
+        }
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(format_err!("Varint at byte {} overflows 64 bits", start))
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+pub fn encode(values: &[i64]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    write_varint(values.len() as u64, &mut bytes);
+    for value in values.iter() {
+        write_varint(zigzag(*value), &mut bytes);
+    }
+
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<i64>> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(format_err!("Not a binary tape"));
+    }
+
+    let mut offset = MAGIC.len();
+    let len = read_varint(bytes, &mut offset)?;
+
+    // Every value takes at least a byte, so a corrupt length can't make
+    // this allocate more than the input
+    let mut values = Vec::with_capacity(len.min((bytes.len() - offset) as u64) as usize);
+    for _ in 0..len {
+        values.push(unzigzag(read_varint(bytes, &mut offset)?));
+    }
+
+    if offset != bytes.len() {
+        return Err(format_err!(
+            "{} trailing byte(s) after {} values",
+            bytes.len() - offset,
+            len
+        ));
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let values = vec![0, 1, -1, 127, -128, 1 << 40, i64::MAX, i64::MIN];
+        assert_eq!(decode(&encode(&values))?, values);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_tapes() {
+        let mut truncated = encode(&[1, 2, 300]);
+        truncated.pop();
+        assert!(decode(&truncated).is_err());
+
+        let mut trailing = encode(&[1, 2, 3]);
+        trailing.push(0);
+        assert!(decode(&trailing).is_err());
+
+        assert!(decode(b"1,2,3").is_err());
+    }
+}
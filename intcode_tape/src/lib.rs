@@ -0,0 +1,7 @@
+// The two Intcode tape formats, shared by every day's VM: comma separated
+// text, and the compact binary format written by the intcode_tape tool.
+mod binary;
+mod text;
+
+pub use binary::{decode, encode, MAGIC};
+pub use text::{parse_values, to_text};
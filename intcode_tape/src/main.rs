@@ -0,0 +1,51 @@
+// Converts Intcode programs between the comma separated text format and the
+// compact binary one. Either format is accepted as input, the same way
+// `Program::from_file` loads them.
+use anyhow::{format_err, Context, Result};
+
+fn load(filename: &str) -> Result<Vec<i64>> {
+    let bytes = std::fs::read(filename).with_context(|| format!("Failed to read {}", filename))?;
+
+    let values = if bytes.starts_with(intcode_tape::MAGIC) {
+        intcode_tape::decode(&bytes).with_context(|| format!("Bad binary tape {}", filename))?
+    } else {
+        intcode_tape::parse_values(std::str::from_utf8(&bytes)?)
+            .with_context(|| format!("Bad text tape {}", filename))?
+    };
+
+    if values.is_empty() {
+        return Err(format_err!("{} holds no program", filename));
+    }
+
+    Ok(values)
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let (command, input, output) = match args.as_slice() {
+        [_, command, input, output] => (command, input, output),
+        _ => {
+            return Err(format_err!(
+                "Usage: intcode_tape <encode|decode> INPUT OUTPUT"
+            ))
+        }
+    };
+
+    let values = load(input)?;
+    let bytes = match command.as_str() {
+        "encode" => intcode_tape::encode(&values),
+        "decode" => intcode_tape::to_text(&values).into_bytes(),
+        _ => return Err(format_err!("Unknown command \"{}\"", command)),
+    };
+
+    std::fs::write(output, &bytes).with_context(|| format!("Failed to write {}", output))?;
+    println!(
+        "Wrote {} value(s) to {} ({} -> {} bytes)",
+        values.len(),
+        output,
+        std::fs::metadata(input)?.len(),
+        bytes.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+
+// Values may be separated by commas, whitespace or both, and `#` starts a
+// comment that runs to the end of the line
+pub fn parse_values(input: &str) -> Result<Vec<i64>> {
+    let mut values = Vec::new();
+
+    for (number, line) in input.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        for field in line.split(|c: char| c == ',' || c.is_whitespace()) {
+            if field.is_empty() {
+                continue;
+            }
+            values.push(
+                field.parse().with_context(|| {
+                    format!("Invalid value \"{}\" on line {}", field, number + 1)
+                })?,
+            );
+        }
+    }
+
+    Ok(values)
+}
+
+pub fn to_text(values: &[i64]) -> String {
+    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    format!("{}\n", values.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whitespace() -> Result<()> {
+        assert_eq!(parse_values(" 1, 2 ,3\t4 ")?, vec![1, 2, 3, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_newlines() -> Result<()> {
+        assert_eq!(parse_values("1,2,\n3\r\n,4\n\n")?, vec![1, 2, 3, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_comments() -> Result<()> {
+        assert_eq!(
+            parse_values("# header\n1,2 # add\n# 5,6\n3,4")?,
+            vec![1, 2, 3, 4]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_value() {
+        assert_eq!(
+            parse_values("1,2\n3,x4").unwrap_err().to_string(),
+            "Invalid value \"x4\" on line 2"
+        );
+    }
+}